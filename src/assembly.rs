@@ -0,0 +1,145 @@
+use crate::glv::GLV;
+use crate::integrate;
+use rand::prelude::*;
+
+// knobs for assemble, the defaults match the coexistence_search thresholds
+#[derive(Debug, Clone)]
+pub struct AssemblyParams {
+    pub invader_density: f64,  // Density an invader is introduced at
+    pub extinct_below: f64,  // Residents under this are removed
+    pub dt: f64,
+    pub tol: f64,  // Steady state when every |x'| is below this
+    pub max_time: f64,  // Give up waiting for a steady state after this long
+}
+
+impl Default for AssemblyParams {
+    fn default() -> AssemblyParams {
+        AssemblyParams {
+            invader_density: 0.01,
+            extinct_below: 0.01,
+            dt: 0.01,
+            tol: 1e-6,
+            max_time: 200.0,
+        }
+    }
+}
+
+// one invasion attempt, species are indices into the pool
+#[derive(Debug, Clone)]
+pub struct AssemblyEvent {
+    pub invader: usize,
+    pub success: bool,  // invader still present at the new steady state
+    pub extinctions: Vec<usize>,  // residents lost in the cascade
+    pub community: Vec<usize>,  // who is left afterwards
+    pub converged: bool,  // whether the run settled before max_time
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Assembly {
+    pub history: Vec<AssemblyEvent>,
+    pub community: Vec<usize>,  // final species, in the order they arrived
+    pub abundances: Vec<f64>,  // final densities, aligned with community
+}
+
+impl Assembly {
+    pub fn new() -> Assembly {
+        return Assembly::default()
+    }
+
+    // one invasion into the current community, recorded in the history.
+    // invaders that are already present are skipped
+    pub fn invade(&mut self, pool: &GLV, invader: usize, params: &AssemblyParams) {
+        if self.community.contains(&invader) {
+            return
+        }
+        // residents start where they settled, the invader starts rare
+        let mut members = self.community.clone();
+        members.push(invader);
+        let mut state = self.abundances.clone();
+        state.push(params.invader_density);
+
+        let sub = pool.subsystem(&members);
+        let steady = integrate::run_to_steady(&sub, state, params.dt, params.tol, params.max_time);
+
+        // drop everyone who fell under the extinction threshold
+        let mut survivors: Vec<usize> = Vec::new();
+        let mut survivor_x: Vec<f64> = Vec::new();
+        let mut extinctions: Vec<usize> = Vec::new();
+        for k in 0..members.len() {
+            if steady.state[k] > params.extinct_below {
+                survivors.push(members[k]);
+                survivor_x.push(steady.state[k]);
+            } else if members[k] != invader {
+                extinctions.push(members[k]);
+            }
+        }
+
+        self.history.push(AssemblyEvent {
+            invader: invader,
+            success: survivors.contains(&invader),
+            extinctions: extinctions,
+            community: survivors.clone(),
+            converged: steady.converged,
+        });
+        self.community = survivors;
+        self.abundances = survivor_x;
+    }
+}
+
+// add species one at a time from the pool in the given order
+pub fn assemble(pool: &GLV, order: &Vec<usize>, params: &AssemblyParams) -> Assembly {
+    let mut out = Assembly::new();
+    for invader in order {
+        out.invade(pool, *invader, params);
+    }
+    return out
+}
+
+// n_invasions draws, each uniform over the species currently absent
+pub fn assemble_random(pool: &GLV, n_invasions: usize, params: &AssemblyParams) -> Assembly {
+    let mut rng = thread_rng();
+    let mut out = Assembly::new();
+    for _ in 0..n_invasions {
+        let absent: Vec<usize> = (0..pool.n).filter(|i| !out.community.contains(i)).collect();
+        match absent.choose(&mut rng) {
+            Some(invader) => out.invade(pool, *invader, params),
+            None => break,
+        }
+    }
+    return out
+}
+
+#[cfg(test)]
+mod test_assembly {
+    use super::*;
+
+    #[test]
+    fn test_assemble_cascade() {
+        // 0 and 1 coexist, 2 beats both of them
+        let mut pool = GLV::new(3);
+        pool.a = vec![vec![1.0, 0.5, 2.0],
+                      vec![0.5, 1.0, 2.0],
+                      vec![0.1, 0.1, 1.0]];
+
+        let out = assemble(&pool, &vec![0, 1, 0, 2], &AssemblyParams::default());
+
+        // the repeat of 0 is skipped
+        assert_eq!(out.history.len(), 3);
+        assert!(out.history[1].success);
+        assert_eq!(out.history[1].community, vec![0, 1]);
+        assert!(out.history[2].success);
+        assert_eq!(out.history[2].extinctions, vec![0, 1]);
+        assert_eq!(out.community, vec![2]);
+        assert!((out.abundances[0] - 1.0).abs() < 1e-3)
+    }
+
+    #[test]
+    fn test_assemble_random() {
+        let pool = GLV::new(4);
+        let out = assemble_random(&pool, 10, &AssemblyParams::default());
+
+        // non interacting species all get in and then there is no one left
+        assert_eq!(out.history.len(), 4);
+        assert_eq!(out.community.len(), 4)
+    }
+}
@@ -0,0 +1,250 @@
+use crate::integrate::{self, Dynamics};
+use crate::lyapunov::{self, LyapunovParams};
+use crate::trajectory::{Record, Trajectory};
+
+// what the long run behaviour looks like
+#[derive(Debug, Clone, PartialEq)]
+pub enum Attractor {
+    FixedPoint { state: Vec<f64> },
+    LimitCycle { period: f64, amplitude: Vec<f64> },  // amplitude is half the peak to peak
+    Heteroclinic { dwell_times: Vec<f64> },  // time each dominant species held on
+    Chaotic { lyapunov: f64 },
+    Unresolved,  // none of the above, eg a transient that did not settle
+}
+
+// knobs for the classification, the trajectory is judged on its second half
+#[derive(Debug, Clone)]
+pub struct ClassifyParams {
+    pub fixed_tol: f64,  // largest peak to peak swing that still counts as settled
+    pub cycle_corr: f64,  // autocorrelation a repeat has to reach to count as periodic
+    pub rare: f64,  // heteroclinic cycles bring every species below this
+    pub chaos_above: f64,  // lyapunov exponents above this count as chaos
+}
+
+impl Default for ClassifyParams {
+    fn default() -> ClassifyParams {
+        ClassifyParams {
+            fixed_tol: 1e-4,
+            cycle_corr: 0.9,
+            rare: 1e-3,
+            chaos_above: 1e-3,
+        }
+    }
+}
+
+// classify from a recorded trajectory (Full or Every). chaos can only be
+// called with a lyapunov exponent, otherwise irregular runs are Unresolved
+pub fn classify_trajectory(traj: &Trajectory, lyapunov: Option<f64>, params: &ClassifyParams) -> Attractor {
+    let len = traj.len();
+    if len < 4 {
+        return Attractor::Unresolved
+    }
+    let start = len / 2;
+    let n = traj.n;
+
+    let mut lo = vec![f64::INFINITY; n];
+    let mut hi = vec![f64::NEG_INFINITY; n];
+    for k in start..len {
+        for i in 0..n {
+            lo[i] = lo[i].min(traj[k][i]);
+            hi[i] = hi[i].max(traj[k][i]);
+        }
+    }
+    let swing = (0..n).map(|i| hi[i] - lo[i]).fold(0.0, f64::max);
+    if swing < params.fixed_tol {
+        return Attractor::FixedPoint { state: traj[len - 1].to_vec() }
+    }
+
+    if let Some(dwell_times) = heteroclinic_dwells(traj, start, &lo, &hi, params) {
+        return Attractor::Heteroclinic { dwell_times: dwell_times }
+    }
+
+    if let Some(lag) = cycle_lag(traj, start, params) {
+        let sample_dt = (traj.t[len - 1] - traj.t[start]) / ((len - 1 - start) as f64);
+        return Attractor::LimitCycle {
+            period: (lag as f64) * sample_dt,
+            amplitude: (0..n).map(|i| 0.5 * (hi[i] - lo[i])).collect(),
+        }
+    }
+
+    match lyapunov {
+        Some(lambda) if lambda > params.chaos_above => Attractor::Chaotic { lyapunov: lambda },
+        _ => Attractor::Unresolved,
+    }
+}
+
+// simulate, drop the transient and classify, only paying for the lyapunov
+// exponent when the cheap checks come up empty. stochastic models have no
+// exponent, so they stop at the cheap checks
+pub fn classify<D: Dynamics>(model: &D, state: Vec<f64>, stop: f64, dt: f64,
+                             params: &ClassifyParams) -> Attractor {
+    let traj = integrate::simulate(model, state, stop, dt, &Record::Full);
+    return classify_recorded(model, &traj, stop, dt, params)
+}
+
+// the same for a trajectory of model that is already there (Full or Every),
+// the lyapunov exponent is run on from its last state for another stop
+pub fn classify_recorded<D: Dynamics>(model: &D, traj: &Trajectory, stop: f64, dt: f64,
+                                      params: &ClassifyParams) -> Attractor {
+    let cheap = classify_trajectory(traj, None, params);
+    if (cheap != Attractor::Unresolved) || model.stochastic() || traj.is_empty() {
+        return cheap
+    }
+    let lyap = LyapunovParams { dt: dt, transient: 0.0, time: stop, ..LyapunovParams::default() };
+    let end = traj.last().unwrap().to_vec();
+    let lambda = lyapunov::max_lyapunov(model, end, &lyap);
+    return classify_trajectory(traj, Some(lambda), params)
+}
+
+// May-Leonard style loops: the dominant species keeps changing, everyone
+// keeps getting close to zero and each stint of dominance is longer than the
+// last. gives the dwell times when that is what we see
+fn heteroclinic_dwells(traj: &Trajectory, start: usize, lo: &Vec<f64>, hi: &Vec<f64>,
+                       params: &ClassifyParams) -> Option<Vec<f64>> {
+    // species that take part in the loop are common at some point
+    let cycling: Vec<usize> = (0..traj.n).filter(|i| hi[*i] > params.rare).collect();
+    if (cycling.len() < 3) || cycling.iter().any(|i| lo[*i] > params.rare) {
+        return None
+    }
+
+    let dominant = |k: usize| -> usize {
+        let row = &traj[k];
+        let mut best = 0;
+        for i in 1..row.len() {
+            if row[i] > row[best] {
+                best = i;
+            }
+        }
+        best
+    };
+    let mut switches: Vec<f64> = Vec::new();
+    let mut current = dominant(start);
+    for k in (start + 1)..traj.len() {
+        let now = dominant(k);
+        if now != current {
+            switches.push(traj.t[k]);
+            current = now;
+        }
+    }
+    // dwell times grow geometrically, so two of them is often all we get
+    if switches.len() < 3 {
+        return None
+    }
+    let dwell_times: Vec<f64> = (1..switches.len()).map(|k| switches[k] - switches[k - 1]).collect();
+
+    // noise at a switch can add a short blip, so ask for a clear overall trend
+    let growing = (1..dwell_times.len()).filter(|k| dwell_times[*k] > dwell_times[*k - 1]).count();
+    let first = dwell_times[0];
+    let last = dwell_times[dwell_times.len() - 1];
+    if (2 * growing > dwell_times.len() - 1) && (last > first) {
+        return Some(dwell_times)
+    }
+    return None
+}
+
+// lag of the first autocorrelation peak of the most variable species, if it
+// is strong enough to call the orbit periodic
+fn cycle_lag(traj: &Trajectory, start: usize, params: &ClassifyParams) -> Option<usize> {
+    let len = traj.len() - start;
+    let mut best = 0;
+    let mut best_var = -1.0;
+    let mut mean = 0.0;
+    for i in 0..traj.n {
+        let m: f64 = (start..traj.len()).map(|k| traj[k][i]).sum::<f64>() / (len as f64);
+        let var: f64 = (start..traj.len()).map(|k| (traj[k][i] - m) * (traj[k][i] - m)).sum();
+        if var > best_var {
+            best = i;
+            best_var = var;
+            mean = m;
+        }
+    }
+    if best_var <= 0.0 {
+        return None
+    }
+    let series: Vec<f64> = (start..traj.len()).map(|k| traj[k][best] - mean).collect();
+    let acf = |lag: usize| -> f64 {
+        let mut acc = 0.0;
+        for k in 0..(len - lag) {
+            acc += series[k] * series[k + lag];
+        }
+        acc / best_var
+    };
+
+    // first dip below zero, then the highest point before the next dip
+    let max_lag = len / 2;
+    let mut lag = 1;
+    while (lag < max_lag) && (acf(lag) > 0.0) {
+        lag += 1;
+    }
+    let mut peak_lag = 0;
+    let mut peak = f64::NEG_INFINITY;
+    let mut below = true;
+    while lag < max_lag {
+        let c = acf(lag);
+        if c > 0.0 {
+            below = false;
+            if c > peak {
+                peak = c;
+                peak_lag = lag;
+            }
+        } else if !below {
+            break;
+        }
+        lag += 1;
+    }
+    // the acf is biased down by (len - lag) / len, undo that before comparing
+    if (peak_lag > 0) && (peak * (len as f64) / ((len - peak_lag) as f64) > params.cycle_corr) {
+        return Some(peak_lag)
+    }
+    return None
+}
+
+#[cfg(test)]
+mod test_attractor {
+    use super::*;
+    use crate::glv::GLV;
+
+    #[test]
+    fn test_fixed_point() {
+        let mut glv = GLV::new(2);
+        glv.a[0][1] = 0.5;
+        glv.a[1][0] = 0.5;
+        let out = classify(&glv, vec![0.2, 0.9], 60.0, 0.01, &ClassifyParams::default());
+
+        match out {
+            Attractor::FixedPoint { state } => assert!((state[0] - 2.0 / 3.0).abs() < 1e-3),
+            other => panic!("expected a fixed point, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_limit_cycle() {
+        let mut traj = Trajectory::with_capacity(2, 2000);
+        for k in 0..2000 {
+            let t = (k as f64) * 0.01;
+            traj.push(t, &vec![1.0 + 0.5 * (t * 2.0).sin(), 1.0]);
+        }
+
+        match classify_trajectory(&traj, None, &ClassifyParams::default()) {
+            Attractor::LimitCycle { period, amplitude } => {
+                assert!((period - std::f64::consts::PI).abs() < 0.05);
+                assert!((amplitude[0] - 0.5).abs() < 1e-3);
+            },
+            other => panic!("expected a limit cycle, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_may_leonard() {
+        let mut glv = GLV::new(3);
+        glv.a = vec![vec![1.0, 0.8, 1.3],
+                     vec![1.3, 1.0, 0.8],
+                     vec![0.8, 1.3, 1.0]];
+        let out = classify(&glv, vec![0.5, 0.3, 0.2], 400.0, 0.01, &ClassifyParams::default());
+
+        match out {
+            Attractor::Heteroclinic { dwell_times } => assert!(dwell_times[1] > dwell_times[0]),
+            other => panic!("expected a heteroclinic cycle, got {:?}", other),
+        }
+    }
+}
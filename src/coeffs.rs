@@ -0,0 +1,196 @@
+use rand::Rng;
+use rand_distr::{Beta, Distribution, Exp, Gamma, LogNormal, Normal, Uniform};
+
+// distributions we draw model coefficients from
+#[derive(Debug, Clone, PartialEq)]
+pub enum Dist {
+    Fixed(f64),
+    Uniform(f64, f64),  // low, high
+    Normal(f64, f64),  // mean, standard deviation
+    LogNormal(f64, f64),  // mean and sd of the underlying normal
+    Gamma(f64, f64),  // shape, scale
+    Beta(f64, f64),  // alpha, beta
+    Exp(f64),  // rate
+}
+
+impl Dist {
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> f64 {
+        match *self {
+            Dist::Fixed(v) => v,
+            Dist::Uniform(lo, hi) => {
+                assert!(lo < hi, "uniform needs low < high");
+                Uniform::new(lo, hi).sample(rng)
+            },
+            Dist::Normal(mu, sd) => Normal::new(mu, sd).expect("bad normal parameters").sample(rng),
+            Dist::LogNormal(mu, sd) => LogNormal::new(mu, sd).expect("bad lognormal parameters").sample(rng),
+            Dist::Gamma(k, theta) => Gamma::new(k, theta).expect("bad gamma parameters").sample(rng),
+            Dist::Beta(a, b) => Beta::new(a, b).expect("bad beta parameters").sample(rng),
+            Dist::Exp(rate) => Exp::new(rate).expect("bad exponential parameters").sample(rng),
+        }
+    }
+}
+
+// how randomize_coeffs fills in a model. the default reproduces the original
+// scheme: unit diagonal, Exp(1) strengths on edges and r left alone
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoeffSpec {
+    pub diag: Dist,  // self limitation a_ii
+    pub off_diag: Dist,  // strength on template edges
+    pub p_negative: f64,  // chance an edge strength gets its sign flipped
+    pub growth: Option<Dist>,  // resample r if set
+}
+
+impl Default for CoeffSpec {
+    fn default() -> CoeffSpec {
+        CoeffSpec {
+            diag: Dist::Fixed(1.0),
+            off_diag: Dist::Exp(1.0),
+            p_negative: 0.0,
+            growth: None,
+        }
+    }
+}
+
+impl CoeffSpec {
+    pub fn sample_diag<R: Rng>(&self, rng: &mut R) -> f64 {
+        return self.diag.sample(rng)
+    }
+
+    pub fn sample_edge<R: Rng>(&self, rng: &mut R) -> f64 {
+        let strength = self.off_diag.sample(rng);
+        if (self.p_negative > 0.0) && (rng.gen::<f64>() < self.p_negative) {
+            return -strength
+        }
+        return strength
+    }
+}
+
+// sign class of one entry a_ij, ie what species j does to species i. with
+// x_i' = r_i x_i (1 - sum_j a_ij x_j) harm is a positive a_ij
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Effect {
+    Absent,
+    Harm,
+    Benefit,
+}
+
+// pairwise interaction classes, asymmetric ones read as "i does this to j"
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interaction {
+    Competition,  // both harmed
+    Mutualism,  // both benefit
+    Predation,  // i eats j
+    Amensalism,  // i harms j, unaffected itself
+    Commensalism,  // i benefits j, unaffected itself
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignedTemplate {
+    pub n: usize,
+    pub effects: Vec<Vec<Effect>>,
+}
+
+impl SignedTemplate {
+    pub fn new(n: usize) -> SignedTemplate {
+        SignedTemplate {
+            n: n,
+            effects: vec![vec![Effect::Absent; n]; n],
+        }
+    }
+
+    // the old 0/1 graphs where every edge is competitive
+    pub fn from_bits(template: &Vec<Vec<u8>>) -> SignedTemplate {
+        let mut out = SignedTemplate::new(template.len());
+        for i in 0..template.len() {
+            for j in 0..template[i].len() {
+                if (i != j) && (template[i][j] == 1) {
+                    out.effects[i][j] = Effect::Harm;
+                }
+            }
+        }
+        return out
+    }
+
+    // integer coded pairs so a GA can evolve them. codes[i][j] for i < j is
+    // 0 for no link, 1 to 5 for the Interaction variants with i as actor and
+    // 6 to 8 for predation, amensalism and commensalism with j as actor
+    pub fn from_codes(codes: &Vec<Vec<u8>>) -> SignedTemplate {
+        let mut out = SignedTemplate::new(codes.len());
+        for i in 0..codes.len() {
+            for j in (i + 1)..codes[i].len() {
+                match codes[i][j] {
+                    1 => out.set(i, j, Interaction::Competition),
+                    2 => out.set(i, j, Interaction::Mutualism),
+                    3 => out.set(i, j, Interaction::Predation),
+                    4 => out.set(i, j, Interaction::Amensalism),
+                    5 => out.set(i, j, Interaction::Commensalism),
+                    6 => out.set(j, i, Interaction::Predation),
+                    7 => out.set(j, i, Interaction::Amensalism),
+                    8 => out.set(j, i, Interaction::Commensalism),
+                    _ => (),
+                }
+            }
+        }
+        return out
+    }
+
+    // overwrites both a_ij and a_ji
+    pub fn set(&mut self, i: usize, j: usize, kind: Interaction) {
+        assert!(i != j);
+        let (on_i, on_j) = match kind {
+            Interaction::Competition => (Effect::Harm, Effect::Harm),
+            Interaction::Mutualism => (Effect::Benefit, Effect::Benefit),
+            Interaction::Predation => (Effect::Benefit, Effect::Harm),
+            Interaction::Amensalism => (Effect::Absent, Effect::Harm),
+            Interaction::Commensalism => (Effect::Absent, Effect::Benefit),
+        };
+        self.effects[i][j] = on_i;
+        self.effects[j][i] = on_j;
+    }
+}
+
+#[cfg(test)]
+mod test_coeffs {
+    use super::*;
+
+    #[test]
+    fn test_dist_sample() {
+        let mut rng = rand::thread_rng();
+        assert_eq!(Dist::Fixed(0.3).sample(&mut rng), 0.3);
+        for _ in 0..100 {
+            let u = Dist::Uniform(0.5, 1.0).sample(&mut rng);
+            let b = Dist::Beta(2.0, 2.0).sample(&mut rng);
+            assert!((u >= 0.5) & (u < 1.0));
+            assert!((b > 0.0) & (b < 1.0));
+        }
+    }
+
+    #[test]
+    fn test_sample_edge_sign() {
+        let mut rng = rand::thread_rng();
+        let spec = CoeffSpec {
+            off_diag: Dist::Fixed(2.0),
+            p_negative: 1.0,
+            ..CoeffSpec::default()
+        };
+        assert_eq!(spec.sample_edge(&mut rng), -2.0)
+    }
+
+    #[test]
+    fn test_signed_template_codes() {
+        let codes = vec![vec![0, 3, 7],
+                         vec![0, 0, 2],
+                         vec![0, 0, 0]];
+        let template = SignedTemplate::from_codes(&codes);
+
+        // 0 eats 1
+        assert_eq!(template.effects[0][1], Effect::Benefit);
+        assert_eq!(template.effects[1][0], Effect::Harm);
+        // 2 harms 0 without cost
+        assert_eq!(template.effects[0][2], Effect::Harm);
+        assert_eq!(template.effects[2][0], Effect::Absent);
+        // 1 and 2 help each other
+        assert_eq!(template.effects[1][2], Effect::Benefit);
+        assert_eq!(template.effects[2][1], Effect::Benefit)
+    }
+}
@@ -0,0 +1,222 @@
+use crate::glv::GLV;
+use crate::integrate::{self, Dynamics};
+use crate::vmath;
+use std::io::{Error, Write};
+
+// the knob we turn
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Parameter {
+    OffDiagonalScale,  // every a_ij with i != j times the value
+    Growth(usize),  // r_i
+    Interaction(usize, usize),  // a_ij
+}
+
+// what changed between a point and the one before it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Bifurcation {
+    Fold,  // the branch ran out, newton stopped converging (saddle-node)
+    BranchPoint,  // a real eigenvalue crossed zero on a branch that carries on, eg transcritical
+    Hopf,  // a complex pair crossed the imaginary axis
+    FeasibilityLoss,  // the branch left the positive orthant
+}
+
+#[derive(Debug, Clone)]
+pub struct ContinuationPoint {
+    pub value: f64,
+    pub state: Vec<f64>,
+    pub converged: bool,  // newton found an equilibrium
+    pub feasible: bool,
+    pub max_real: f64,  // largest real part of the jacobian's eigenvalues
+    pub max_real_imag: f64,  // imaginary part that goes with it
+    pub stable: bool,
+    pub bifurcation: Option<Bifurcation>,  // detected between this point and the last
+}
+
+impl Parameter {
+    pub fn apply(&self, model: &mut GLV, base: &GLV, value: f64) {
+        match *self {
+            Parameter::OffDiagonalScale => {
+                for i in 0..model.n {
+                    for j in 0..model.n {
+                        if i != j {
+                            model.a[i][j] = base.a[i][j] * value;
+                        }
+                    }
+                }
+            },
+            Parameter::Growth(i) => model.r[i] = value,
+            Parameter::Interaction(i, j) => model.a[i][j] = value,
+        }
+    }
+}
+
+// newton's method on x' = 0 from a warm start
+fn newton<D: Dynamics>(model: &D, start: &Vec<f64>) -> Option<Vec<f64>> {
+    let n = model.dim();
+    let mut x = start.clone();
+    let mut f = vec![0.0; n];
+    for _ in 0..50 {
+        model.derivative(0.0, &x, &mut f);
+        if f.iter().all(|fi| fi.abs() < 1e-12) {
+            return Some(x)
+        }
+        let jac = integrate::jacobian(model, 0.0, &x);
+        let neg_f: Vec<f64> = f.iter().map(|fi| -fi).collect();
+        let dx = vmath::solve(&jac, &neg_f)?;
+        for i in 0..n {
+            x[i] += dx[i];
+        }
+        if dx.iter().all(|d| d.abs() < 1e-13) {
+            return Some(x)
+        }
+    }
+    return None
+}
+
+// follow the equilibrium that start sits on (or near) across values, each
+// solve warm started from the previous one. the model is left untouched
+pub fn continuation(model: &GLV, param: Parameter, values: &Vec<f64>, start: &Vec<f64>) -> Vec<ContinuationPoint> {
+    let mut current = model.clone();
+    let mut guess = start.clone();
+    let mut out: Vec<ContinuationPoint> = Vec::with_capacity(values.len());
+
+    for value in values {
+        param.apply(&mut current, model, *value);
+        let (state, converged) = match newton(&current, &guess) {
+            Some(x) => (x, true),
+            None => (guess.clone(), false),
+        };
+
+        let jac = integrate::jacobian(&current, 0.0, &state);
+        let (max_real, max_real_imag) = match vmath::eigenvalues(&jac) {
+            Some(ev) => ev.iter().cloned().fold((f64::NEG_INFINITY, 0.0), |best, e| if e.0 > best.0 { e } else { best }),
+            None => (f64::NAN, 0.0),
+        };
+
+        let mut point = ContinuationPoint {
+            value: *value,
+            feasible: GLV::is_feasible(&state),
+            state: state,
+            converged: converged,
+            max_real: max_real,
+            max_real_imag: max_real_imag,
+            stable: max_real < 0.0,
+            bifurcation: None,
+        };
+        if let Some(prev) = out.last() {
+            point.bifurcation = detect(prev, &point);
+        }
+        if converged {
+            guess = point.state.clone();
+        }
+        out.push(point);
+    }
+    return out
+}
+
+// a fold shows up as the tracked equilibrium disappearing, the step can not
+// tell that apart from newton just losing it so it is worth a finer sweep
+fn detect(prev: &ContinuationPoint, next: &ContinuationPoint) -> Option<Bifurcation> {
+    if prev.converged && !next.converged {
+        return Some(Bifurcation::Fold)
+    }
+    if !(prev.converged && next.converged) {
+        return None
+    }
+    if prev.feasible != next.feasible {
+        return Some(Bifurcation::FeasibilityLoss)
+    }
+    if (prev.max_real < 0.0) != (next.max_real < 0.0) {
+        // the pair has to be complex on both sides to call it hopf
+        if (prev.max_real_imag.abs() > 1e-8) && (next.max_real_imag.abs() > 1e-8) {
+            return Some(Bifurcation::Hopf)
+        }
+        return Some(Bifurcation::BranchPoint)
+    }
+    return None
+}
+
+// value, x_0..x_n-1, max_real, max_real_imag, stable, feasible, bifurcation
+pub fn write_csv<W: Write>(out: &mut W, points: &Vec<ContinuationPoint>) -> Result<(), Error> {
+    if points.is_empty() {
+        return Ok(())
+    }
+    write!(out, "value,")?;
+    for i in 0..points[0].state.len() {
+        write!(out, "x{},", i)?;
+    }
+    writeln!(out, "max_real,max_real_imag,stable,feasible,bifurcation")?;
+    for p in points {
+        write!(out, "{},", p.value)?;
+        for x in p.state.iter() {
+            write!(out, "{},", x)?;
+        }
+        let bif = match p.bifurcation {
+            Some(Bifurcation::Fold) => "fold",
+            Some(Bifurcation::BranchPoint) => "branch",
+            Some(Bifurcation::Hopf) => "hopf",
+            Some(Bifurcation::FeasibilityLoss) => "feasibility",
+            None => "",
+        };
+        writeln!(out, "{},{},{},{},{}", p.max_real, p.max_real_imag, p.stable, p.feasible, bif)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test_continuation {
+    use super::*;
+    use crate::immigration::Immigration;
+
+    fn sweep(lo: f64, hi: f64, steps: usize) -> Vec<f64> {
+        (0..steps).map(|k| lo + (hi - lo) * (k as f64) / ((steps - 1) as f64)).collect()
+    }
+
+    #[test]
+    fn test_hopf_in_may_leonard() {
+        // the complex pair has real part -x* (1 - s (alpha + beta) / 2), which
+        // crosses zero at s = 2 / 2.1
+        let mut glv = GLV::new(3);
+        glv.a = vec![vec![1.0, 0.8, 1.3],
+                     vec![1.3, 1.0, 0.8],
+                     vec![0.8, 1.3, 1.0]];
+        let points = continuation(&glv, Parameter::OffDiagonalScale, &sweep(0.8, 1.1, 31), &vec![0.4, 0.4, 0.4]);
+
+        let hopf: Vec<&ContinuationPoint> = points.iter().filter(|p| p.bifurcation == Some(Bifurcation::Hopf)).collect();
+        assert_eq!(hopf.len(), 1);
+        assert!((hopf[0].value - 0.96).abs() < 1e-9);
+        assert!(points.iter().all(|p| p.converged && p.feasible))
+    }
+
+    #[test]
+    fn test_fold() {
+        // logistic growth with a constant yield taken off, x' = r x (1 - x) - c.
+        // the two equilibria meet and vanish at r = 4c
+        let mut glv = GLV::new(1);
+        glv.immigration = Immigration::Constant(vec![-0.1]);
+        let points = continuation(&glv, Parameter::Growth(0), &sweep(1.05, 0.25, 9), &vec![0.9]);
+
+        assert!(points[..7].iter().all(|p| p.converged && p.stable));
+        assert_eq!(points[7].bifurcation, Some(Bifurcation::Fold));
+        assert!(!points[7].converged);
+        assert!(points.iter().filter(|p| p.bifurcation.is_some()).count() == 1)
+    }
+
+    #[test]
+    fn test_branch_point_and_table() {
+        // symmetric competition loses stability at s = 1
+        let mut glv = GLV::new(2);
+        glv.a[0][1] = 1.0;
+        glv.a[1][0] = 1.0;
+        let points = continuation(&glv, Parameter::OffDiagonalScale, &sweep(0.55, 1.45, 10), &vec![0.6, 0.6]);
+
+        assert_eq!(points[5].bifurcation, Some(Bifurcation::BranchPoint));
+        assert!(points[4].stable && !points[5].stable);
+
+        let mut table: Vec<u8> = Vec::new();
+        write_csv(&mut table, &points).unwrap();
+        let text = String::from_utf8(table).unwrap();
+        assert_eq!(text.lines().count(), 11);
+        assert!(text.starts_with("value,x0,x1,"))
+    }
+}
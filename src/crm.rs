@@ -0,0 +1,156 @@
+use crate::coeffs::Dist;
+use crate::glv::GLV;
+use crate::integrate::{self, Dynamics};
+use crate::trajectory::{Record, Trajectory};
+use rand::prelude::*;
+
+// MacArthur consumer resource model. the state is the n consumers followed
+// by the m resources:
+//   N_i' = N_i (sum_k w_k c_ik R_k - mort_i)
+//   R_k' = (supply_k / K_k) R_k (K_k - R_k) - sum_i N_i c_ik R_k
+#[derive(Debug, Clone)]
+pub struct CRM {
+    pub n: usize,  // Number of consumer species
+    pub m: usize,  // Number of resources
+    pub c: Vec<Vec<f64>>,  // Uptake rate of resource k by species i
+    pub w: Vec<f64>,  // Value of a unit of each resource
+    pub mort: Vec<f64>,  // Maintenance cost of each species
+    pub supply: Vec<f64>,  // Intrinsic resupply rate of each resource
+    pub capacity: Vec<f64>,  // Resource carrying capacities
+}
+
+impl CRM {
+    pub fn new(n_species: usize, n_resources: usize) -> CRM {
+        CRM {
+            n: n_species,
+            m: n_resources,
+            c: vec![vec![0.0; n_resources]; n_species],
+            w: vec![1.0; n_resources],
+            mort: vec![0.0; n_species],
+            supply: vec![1.0; n_resources],
+            capacity: vec![1.0; n_resources],
+        }
+    }
+
+    // template is n x m, uptake is drawn from dist wherever it is 1
+    pub fn randomize_uptake(&mut self, template: &Vec<Vec<u8>>, dist: &Dist) {
+        assert_eq!(template.len(), self.n);
+        assert_eq!(template[0].len(), self.m);
+        let mut rng = thread_rng();
+        for i in 0..self.n {
+            for k in 0..self.m {
+                if template[i][k] == 1 {
+                    self.c[i][k] = dist.sample(&mut rng).abs();
+                } else {
+                    self.c[i][k] = 0.0;
+                }
+            }
+        }
+    }
+
+    // GLV we get with resources at quasi steady state, in the repo's form
+    // x_i' = r_i x_i (1 - sum_j a_ij x_j). species that cannot grow on the
+    // resources at all (r_i = 0) get an empty row
+    pub fn effective_glv(&self) -> GLV {
+        let mut glv = GLV::new(self.n);
+        for i in 0..self.n {
+            let mut growth = -self.mort[i];
+            for k in 0..self.m {
+                growth += self.w[k] * self.c[i][k] * self.capacity[k];
+            }
+            glv.r[i] = growth;
+            for j in 0..self.n {
+                let mut alpha = 0.0;
+                for k in 0..self.m {
+                    alpha += self.w[k] * self.c[i][k] * self.c[j][k] * self.capacity[k] / self.supply[k];
+                }
+                glv.a[i][j] = if growth != 0.0 { alpha / growth } else { 0.0 };
+            }
+        }
+        return glv
+    }
+
+    // consumers and resources stacked into one state vector
+    pub fn state(consumers: &Vec<f64>, resources: &Vec<f64>) -> Vec<f64> {
+        let mut out = consumers.clone();
+        out.extend_from_slice(resources);
+        return out
+    }
+
+    pub fn simulate(model: &CRM, state: Vec<f64>, stop: f64, dt: f64, record: &Record) -> Trajectory {
+        return integrate::simulate(model, state, stop, dt, record)
+    }
+}
+
+impl Dynamics for CRM {
+    fn dim(&self) -> usize {
+        return self.n + self.m
+    }
+
+    fn derivative(&self, _t: f64, x: &Vec<f64>, out: &mut Vec<f64>) {
+        let (consumers, resources) = x.split_at(self.n);
+        for i in 0..self.n {
+            let mut intake = 0.0;
+            for k in 0..self.m {
+                intake += self.w[k] * self.c[i][k] * resources[k];
+            }
+            out[i] = consumers[i] * (intake - self.mort[i]);
+        }
+        for k in 0..self.m {
+            let mut uptake = 0.0;
+            for i in 0..self.n {
+                uptake += consumers[i] * self.c[i][k];
+            }
+            let renewal = (self.supply[k] / self.capacity[k]) * (self.capacity[k] - resources[k]);
+            out[self.n + k] = resources[k] * (renewal - uptake);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_crm {
+    use super::*;
+
+    fn two_by_two() -> CRM {
+        let mut crm = CRM::new(2, 2);
+        crm.c = vec![vec![1.0, 0.2],
+                     vec![0.3, 0.8]];
+        crm.mort = vec![0.1, 0.1];
+        crm.supply = vec![2.0, 3.0];
+        crm.capacity = vec![1.0, 1.5];
+        return crm
+    }
+
+    #[test]
+    fn test_effective_glv() {
+        let crm = two_by_two();
+        let glv = crm.effective_glv();
+        let consumers = vec![0.2, 0.3];
+
+        // put the resources where they would settle for these consumers
+        let mut resources = vec![0.0; 2];
+        for k in 0..2 {
+            let uptake = consumers[0] * crm.c[0][k] + consumers[1] * crm.c[1][k];
+            resources[k] = crm.capacity[k] * (1.0 - uptake / crm.supply[k]);
+        }
+        let mut crm_rate = vec![0.0; 4];
+        crm.derivative(0.0, &CRM::state(&consumers, &resources), &mut crm_rate);
+        let mut glv_rate = vec![0.0; 2];
+        glv.derivative(0.0, &consumers, &mut glv_rate);
+
+        assert!((crm_rate[0] - glv_rate[0]).abs() < 1e-12);
+        assert!((crm_rate[1] - glv_rate[1]).abs() < 1e-12)
+    }
+
+    #[test]
+    fn test_simulate() {
+        let crm = two_by_two();
+        let state = CRM::state(&vec![0.1, 0.1], &vec![1.0, 1.5]);
+        let out = CRM::simulate(&crm, state, 20.0, 0.01, &Record::Every(100));
+
+        assert_eq!(out.n, 4);
+        for i in 0..4 {
+            assert!(out.last().unwrap()[i] > 0.0);
+        }
+    }
+}
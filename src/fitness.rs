@@ -0,0 +1,101 @@
+use crate::genome::MixedGenome;
+use crate::glv::{BatchRun, GLV};
+use crate::resilience;
+use crate::trajectory::Record;
+use crate::utils;
+use rand::Rng;
+
+// GLV constants shared by the fitness functions
+pub const SPECIES: usize = 20;
+pub const COEFFS: usize = 100;
+pub const STARTS: usize = 3;
+pub const SIMTIME: f64 = 30.0;
+pub const SIMTIMEDT: f64 = 0.01;
+
+// random start states, fresh for every run
+fn draw_starts(count: usize) -> Vec<Vec<f64>> {
+    let mut init_states: Vec<Vec<f64>> = Vec::with_capacity(count);
+    for _s in 0..count {
+        let mut init_state: Vec<f64> = vec![0.0; SPECIES];
+        for i in 0..SPECIES {
+            init_state[i] = rand::thread_rng().gen();
+        }
+        init_states.push(init_state);
+    }
+    return init_states
+}
+
+// random coefficient draws on the genome's graph, each run from its own
+// random start conditions. gives back the matrices along with the runs
+pub fn run_draws(genome: &Vec<u8>) -> (Vec<Vec<Vec<f64>>>, Vec<BatchRun>) {
+    // set everything up
+    let graph = GLV::vec_to_mat(genome, SPECIES);
+    let base = GLV::new(SPECIES);
+
+    // draw the coefficient sets and the start conditions each of them runs from
+    let mut matrices: Vec<Vec<Vec<f64>>> = Vec::with_capacity(COEFFS);
+    let mut init_states: Vec<Vec<Vec<f64>>> = Vec::with_capacity(COEFFS);
+    for _c in 0..COEFFS {
+        let mut glv_sim = GLV::new(SPECIES);
+        glv_sim.randomize_coeffs(&graph);
+        matrices.push(glv_sim.a);
+        init_states.push(draw_starts(STARTS));
+    }
+
+    // run the simulations and save the final states
+    let runs = GLV::simulate_batch(&base, &matrices, &init_states, SIMTIME, SIMTIMEDT, &Record::Final, None);
+    return (matrices, runs)
+}
+
+pub fn coexistence_search(genome: &Vec<u8>) -> f64 {
+    let total = COEFFS * STARTS;
+    let (_, runs) = run_draws(genome);
+
+    // We will score each of these end states with species richness
+    let mut s_avg = 0.0;
+    for run in runs.iter() {
+        s_avg += (utils::richness(&run.trajectory.data, 0.01) as f64) / (total as f64);
+    }
+    let s_norm = s_avg / (SPECIES as f64);
+    return s_norm
+}
+
+// like coexistence_search but each end state only counts as much as the
+// surviving community is resilient, so rich but fragile communities lose out
+pub fn robust_coexistence_search(genome: &Vec<u8>) -> f64 {
+    let total = COEFFS * STARTS;
+    let (matrices, runs) = run_draws(genome);
+
+    let mut score = 0.0;
+    for run in runs.iter() {
+        let end = &run.trajectory.data;
+        let survivors: Vec<usize> = (0..SPECIES).filter(|i| end[*i] > 0.01).collect();
+        if survivors.is_empty() {
+            continue;
+        }
+        let mut full = GLV::new(SPECIES);
+        full.a = matrices[run.matrix].clone();
+        // resilience in (0, 1), unstable or infeasible communities score 0
+        let weight = match resilience::analyze(&full.subsystem(&survivors)) {
+            Some(res) if res.stable => res.resilience / (1.0 + res.resilience),
+            _ => 0.0,
+        };
+        score += weight * (survivors.len() as f64) / (total as f64);
+    }
+    return score / (SPECIES as f64)
+}
+
+// fitness for MGA<MixedGenome>, where the genome carries the strengths too.
+// there is one coefficient set per genome so only the start states are drawn
+pub fn weighted_coexistence_search(genome: &MixedGenome) -> f64 {
+    let base = GLV::new(SPECIES);
+    let matrices = vec![genome.to_matrix(SPECIES)];
+    let init_states = vec![draw_starts(STARTS)];
+
+    let runs = GLV::simulate_batch(&base, &matrices, &init_states, SIMTIME, SIMTIMEDT, &Record::Final, None);
+    let mut s_avg = 0.0;
+    for run in runs.iter() {
+        s_avg += (utils::richness(&run.trajectory.data, 0.01) as f64) / (STARTS as f64);
+    }
+    return s_avg / (SPECIES as f64)
+}
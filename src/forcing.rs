@@ -0,0 +1,99 @@
+use std::f64::consts::PI;
+
+// multiplicative factor on a per species parameter as a function of time
+#[derive(Debug, Clone)]
+pub enum Forcing {
+    Constant,
+    // 1 + amplitude_i sin(2 pi t / period + phase_i)
+    Sinusoid { amplitude: Vec<f64>, period: f64, phase: Vec<f64> },
+    // factors[k] holds from times[k] until times[k + 1], 1 before times[0]
+    Piecewise { times: Vec<f64>, factors: Vec<Vec<f64>> },
+    // f(t, i) with i the species index in the original model. ids maps the
+    // local index back to it, so subsets keep calling f with the right i
+    Custom { f: fn(f64, usize) -> f64, ids: Vec<usize> },
+}
+
+impl Forcing {
+    // all species in phase
+    pub fn seasonal(n: usize, amplitude: f64, period: f64) -> Forcing {
+        Forcing::Sinusoid {
+            amplitude: vec![amplitude; n],
+            period: period,
+            phase: vec![0.0; n],
+        }
+    }
+
+    pub fn custom(f: fn(f64, usize) -> f64, n: usize) -> Forcing {
+        return Forcing::Custom { f: f, ids: (0..n).collect() }
+    }
+
+    pub fn factor(&self, t: f64, i: usize) -> f64 {
+        match self {
+            Forcing::Constant => 1.0,
+            Forcing::Sinusoid { amplitude, period, phase } => {
+                1.0 + amplitude[i] * (2.0 * PI * t / period + phase[i]).sin()
+            },
+            Forcing::Piecewise { times, factors } => {
+                // schedules are short, a linear scan is fine
+                let mut factor = 1.0;
+                for k in 0..times.len() {
+                    if t >= times[k] {
+                        factor = factors[k][i];
+                    } else {
+                        break;
+                    }
+                }
+                factor
+            },
+            Forcing::Custom { f, ids } => f(t, ids[i]),
+        }
+    }
+
+    pub fn subset(&self, idx: &Vec<usize>) -> Forcing {
+        match self {
+            Forcing::Constant => Forcing::Constant,
+            Forcing::Sinusoid { amplitude, period, phase } => Forcing::Sinusoid {
+                amplitude: idx.iter().map(|i| amplitude[*i]).collect(),
+                period: *period,
+                phase: idx.iter().map(|i| phase[*i]).collect(),
+            },
+            Forcing::Piecewise { times, factors } => Forcing::Piecewise {
+                times: times.clone(),
+                factors: factors.iter().map(|row| idx.iter().map(|i| row[*i]).collect()).collect(),
+            },
+            Forcing::Custom { f, ids } => Forcing::Custom {
+                f: *f,
+                ids: idx.iter().map(|i| ids[*i]).collect(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_forcing {
+    use super::*;
+
+    fn ramp(t: f64, i: usize) -> f64 {
+        t * (i as f64)
+    }
+
+    #[test]
+    fn test_factor() {
+        let season = Forcing::seasonal(2, 0.5, 4.0);
+        assert!((season.factor(1.0, 0) - 1.5).abs() < 1e-12);
+
+        let schedule = Forcing::Piecewise {
+            times: vec![1.0, 2.0],
+            factors: vec![vec![0.5, 0.5], vec![2.0, 3.0]],
+        };
+        assert_eq!(schedule.factor(0.5, 1), 1.0);
+        assert_eq!(schedule.factor(1.5, 1), 0.5);
+        assert_eq!(schedule.factor(2.5, 1), 3.0)
+    }
+
+    #[test]
+    fn test_custom_subset() {
+        let forcing = Forcing::custom(ramp, 3).subset(&vec![2]);
+        assert_eq!(forcing.factor(1.5, 0), 3.0)
+    }
+}
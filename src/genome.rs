@@ -0,0 +1,376 @@
+use crate::coeffs::Dist;
+use crate::utils::hamming;
+use rand::prelude::*;
+use rand_distr::Normal;
+
+// what the MGA needs to know about a genome: how to draw one, how to
+// mutate it, how a tournament winner infects the loser with its genes and
+// how far apart two genomes are for novelty search. p is the per gene
+// probability of a mutation or an infection. genes is what MGA is given,
+// for the interaction templates that is one per matrix entry, ie species^2
+pub trait Genome: Clone {
+    type Params: Default + Clone;
+
+    fn random<R: Rng>(genes: usize, params: &Self::Params, rng: &mut R) -> Self;
+    fn mutate<R: Rng>(&mut self, p: f64, params: &Self::Params, rng: &mut R);
+    fn infect<R: Rng>(&mut self, winner: &Self, p: f64, params: &Self::Params, rng: &mut R);
+    fn distance(&self, other: &Self) -> f64;
+}
+
+// the original bitstrings, flip to mutate and copy to infect
+impl Genome for Vec<u8> {
+    type Params = ();
+
+    fn random<R: Rng>(genes: usize, _params: &(), rng: &mut R) -> Vec<u8> {
+        return (0..genes).map(|_| rng.gen_range(0..2)).collect()
+    }
+
+    fn mutate<R: Rng>(&mut self, p: f64, _params: &(), rng: &mut R) {
+        for g in self.iter_mut() {
+            if rng.gen::<f64>() < p {
+                *g = (*g + 1) % 2;
+            }
+        }
+    }
+
+    fn infect<R: Rng>(&mut self, winner: &Vec<u8>, p: f64, _params: &(), rng: &mut R) {
+        for k in 0..self.len() {
+            if rng.gen::<f64>() < p {
+                self[k] = winner[k];
+            }
+        }
+    }
+
+    fn distance(&self, other: &Vec<u8>) -> f64 {
+        return hamming(self, other) as f64
+    }
+}
+
+// how infection mixes the continuous genes of winner and loser
+#[derive(Debug, Clone, PartialEq)]
+pub enum Crossover {
+    Copy,  // take the winner's value, like the bits
+    Arithmetic(f64),  // w * winner + (1 - w) * loser
+    Blend(f64),  // BLX-alpha, uniform on the pair's range widened by alpha each side
+}
+
+#[derive(Debug, Clone)]
+pub struct RealParams {
+    pub init: Dist,  // where new weights are drawn from
+    pub sigma: f64,  // sd of the gaussian mutation of a weight
+    pub lower: f64,  // weights are kept inside [lower, upper]
+    pub upper: f64,
+    pub crossover: Crossover,
+}
+
+impl Default for RealParams {
+    // same draw as the off diagonal of CoeffSpec::default
+    fn default() -> RealParams {
+        RealParams {
+            init: Dist::Exp(1.0),
+            sigma: 0.1,
+            lower: 0.0,
+            upper: 5.0,
+            crossover: Crossover::Blend(0.5),
+        }
+    }
+}
+
+// a link bit and a strength for every gene, so topology and interaction
+// strengths evolve together. a gene only matters while its bit is on but
+// the weight is kept (and mutated) either way
+#[derive(Debug, Clone, PartialEq)]
+pub struct MixedGenome {
+    pub bits: Vec<u8>,
+    pub weights: Vec<f64>,
+}
+
+impl MixedGenome {
+    // interaction matrix laid out like GLV::vec_to_mat, with the diagonal
+    // set to self limitation of 1 whatever the genome says
+    pub fn to_matrix(&self, species: usize) -> Vec<Vec<f64>> {
+        let mut a = vec![vec![0.0; species]; species];
+        for i in 0..species {
+            for j in 0..species {
+                let k = i * species + j;
+                a[i][j] = if i == j { 1.0 } else { (self.bits[k] as f64) * self.weights[k] };
+            }
+        }
+        return a
+    }
+}
+
+// real valued genes, gaussian mutation and crossover on infection
+impl Genome for Vec<f64> {
+    type Params = RealParams;
+
+    fn random<R: Rng>(genes: usize, params: &RealParams, rng: &mut R) -> Vec<f64> {
+        return (0..genes).map(|_| params.init.sample(rng).max(params.lower).min(params.upper)).collect()
+    }
+
+    fn mutate<R: Rng>(&mut self, p: f64, params: &RealParams, rng: &mut R) {
+        let kick = Normal::new(0.0, params.sigma).unwrap();
+        for w in self.iter_mut() {
+            if rng.gen::<f64>() < p {
+                *w = (*w + kick.sample(rng)).max(params.lower).min(params.upper);
+            }
+        }
+    }
+
+    fn infect<R: Rng>(&mut self, winner: &Vec<f64>, p: f64, params: &RealParams, rng: &mut R) {
+        for k in 0..self.len() {
+            if rng.gen::<f64>() >= p {
+                continue;
+            }
+            let (w, l) = (winner[k], self[k]);
+            let mixed = match params.crossover {
+                Crossover::Copy => w,
+                Crossover::Arithmetic(share) => share * w + (1.0 - share) * l,
+                Crossover::Blend(alpha) => {
+                    let (lo, hi) = (w.min(l), w.max(l));
+                    let pad = alpha * (hi - lo);
+                    if hi - lo > 0.0 { rng.gen_range((lo - pad)..(hi + pad)) } else { w }
+                },
+            };
+            self[k] = mixed.max(params.lower).min(params.upper);
+        }
+    }
+
+    // manhattan, so it adds up like the hamming distance of the bits
+    fn distance(&self, other: &Vec<f64>) -> f64 {
+        return (0..self.len()).map(|k| (self[k] - other[k]).abs()).sum()
+    }
+}
+
+impl Genome for MixedGenome {
+    type Params = RealParams;
+
+    fn random<R: Rng>(genes: usize, params: &RealParams, rng: &mut R) -> MixedGenome {
+        MixedGenome {
+            bits: Vec::<u8>::random(genes, &(), rng),
+            weights: Vec::<f64>::random(genes, params, rng),
+        }
+    }
+
+    fn mutate<R: Rng>(&mut self, p: f64, params: &RealParams, rng: &mut R) {
+        self.bits.mutate(p, &(), rng);
+        self.weights.mutate(p, params, rng);
+    }
+
+    fn infect<R: Rng>(&mut self, winner: &MixedGenome, p: f64, params: &RealParams, rng: &mut R) {
+        self.bits.infect(&winner.bits, p, &(), rng);
+        self.weights.infect(&winner.weights, p, params, rng);
+    }
+
+    fn distance(&self, other: &MixedGenome) -> f64 {
+        return self.bits.distance(&other.bits) + self.weights.distance(&other.weights)
+    }
+}
+
+// an ordering of genes items, eg the invasion order for assembly::assemble
+#[derive(Debug, Clone, PartialEq)]
+pub struct Permutation(pub Vec<usize>);
+
+impl Genome for Permutation {
+    type Params = ();
+
+    fn random<R: Rng>(genes: usize, _params: &(), rng: &mut R) -> Permutation {
+        let mut order: Vec<usize> = (0..genes).collect();
+        order.shuffle(rng);
+        return Permutation(order)
+    }
+
+    // swap each position with a random other one
+    fn mutate<R: Rng>(&mut self, p: f64, _params: &(), rng: &mut R) {
+        let len = self.0.len();
+        for k in 0..len {
+            if rng.gen::<f64>() < p {
+                let other = rng.gen_range(0..len);
+                self.0.swap(k, other);
+            }
+        }
+    }
+
+    // take the winner's item at a position by swapping it in from wherever
+    // the loser has it, which keeps the loser a permutation
+    fn infect<R: Rng>(&mut self, winner: &Permutation, p: f64, _params: &(), rng: &mut R) {
+        for k in 0..self.0.len() {
+            if rng.gen::<f64>() < p {
+                let from = self.0.iter().position(|item| *item == winner.0[k]).unwrap();
+                self.0.swap(k, from);
+            }
+        }
+    }
+
+    // positions that hold different items
+    fn distance(&self, other: &Permutation) -> f64 {
+        return hamming(&self.0, &other.0) as f64
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GraphParams {
+    pub density: f64,  // chance of each link in a random graph
+    pub symmetric: bool,  // links go both ways
+}
+
+impl Default for GraphParams {
+    fn default() -> GraphParams {
+        GraphParams {
+            density: 0.5,
+            symmetric: false,
+        }
+    }
+}
+
+// an interaction graph, no self loops. like the bitstrings it has a gene for
+// every entry of the matrix, so genes has to be a square. infection works on
+// whole nodes: the loser takes over all the out links of a species from the
+// winner, so local structure is passed on together
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphGenome {
+    pub links: Vec<Vec<u8>>,  // a template for GLV::randomize_coeffs
+}
+
+impl GraphGenome {
+    fn set(&mut self, i: usize, j: usize, on: u8, symmetric: bool) {
+        self.links[i][j] = on;
+        if symmetric {
+            self.links[j][i] = on;
+        }
+    }
+}
+
+impl Genome for GraphGenome {
+    type Params = GraphParams;
+
+    fn random<R: Rng>(genes: usize, params: &GraphParams, rng: &mut R) -> GraphGenome {
+        let n = (genes as f64).sqrt().round() as usize;
+        assert_eq!(n * n, genes, "graph genomes need species^2 genes");
+        let mut g = GraphGenome { links: vec![vec![0; n]; n] };
+        for i in 0..n {
+            let first = if params.symmetric { i + 1 } else { 0 };
+            for j in first..n {
+                if (i != j) && (rng.gen::<f64>() < params.density) {
+                    g.set(i, j, 1, params.symmetric);
+                }
+            }
+        }
+        return g
+    }
+
+    // toggle each possible link
+    fn mutate<R: Rng>(&mut self, p: f64, params: &GraphParams, rng: &mut R) {
+        let n = self.links.len();
+        for i in 0..n {
+            let first = if params.symmetric { i + 1 } else { 0 };
+            for j in first..n {
+                if (i != j) && (rng.gen::<f64>() < p) {
+                    let on = 1 - self.links[i][j];
+                    self.set(i, j, on, params.symmetric);
+                }
+            }
+        }
+    }
+
+    fn infect<R: Rng>(&mut self, winner: &GraphGenome, p: f64, params: &GraphParams, rng: &mut R) {
+        let n = self.links.len();
+        for i in 0..n {
+            if rng.gen::<f64>() < p {
+                for j in 0..n {
+                    self.set(i, j, winner.links[i][j], params.symmetric);
+                }
+            }
+        }
+    }
+
+    // links present in one graph and not the other
+    fn distance(&self, other: &GraphGenome) -> f64 {
+        return (0..self.links.len()).map(|i| hamming(&self.links[i], &other.links[i])).sum::<usize>() as f64
+    }
+}
+
+#[cfg(test)]
+mod test_genome {
+    use super::*;
+
+    #[test]
+    fn test_bits() {
+        let mut rng = thread_rng();
+        let mut g = Vec::<u8>::random(50, &(), &mut rng);
+        assert!(g.iter().all(|b| *b < 2));
+
+        let winner = vec![1; 50];
+        g.infect(&winner, 1.0, &(), &mut rng);
+        assert_eq!(g, winner);
+        g.mutate(1.0, &(), &mut rng);
+        assert!(g.iter().all(|b| *b == 0))
+    }
+
+    #[test]
+    fn test_mixed() {
+        let mut rng = thread_rng();
+        let params = RealParams { upper: 2.0, ..RealParams::default() };
+        let mut g = MixedGenome::random(9, &params, &mut rng);
+        assert!(g.weights.iter().all(|w| (*w >= 0.0) && (*w <= 2.0)));
+
+        let winner = MixedGenome { bits: vec![1; 9], weights: vec![1.0; 9] };
+        let arithmetic = RealParams { crossover: Crossover::Arithmetic(0.5), ..params.clone() };
+        g.weights = vec![0.0; 9];
+        g.infect(&winner, 1.0, &arithmetic, &mut rng);
+        assert_eq!(g.bits, winner.bits);
+        assert!(g.weights.iter().all(|w| *w == 0.5));
+
+        let blend = RealParams { crossover: Crossover::Blend(0.0), ..params.clone() };
+        g.infect(&winner, 1.0, &blend, &mut rng);
+        assert!(g.weights.iter().all(|w| (*w >= 0.5) && (*w <= 1.0)));
+
+        g.mutate(1.0, &params, &mut rng);
+        assert!(g.weights.iter().all(|w| (*w >= 0.0) && (*w <= 2.0)));
+
+        let a = g.to_matrix(3);
+        assert_eq!(a[1][1], 1.0);
+        assert_eq!(a[0][1], (g.bits[1] as f64) * g.weights[1])
+    }
+
+    #[test]
+    fn test_permutation() {
+        let mut rng = thread_rng();
+        let is_perm = |p: &Permutation| {
+            let mut sorted = p.0.clone();
+            sorted.sort();
+            sorted == (0..10).collect::<Vec<usize>>()
+        };
+        let mut g = Permutation::random(10, &(), &mut rng);
+        g.mutate(0.5, &(), &mut rng);
+        assert!(is_perm(&g));
+
+        let winner = Permutation((0..10).rev().collect());
+        g.infect(&winner, 0.5, &(), &mut rng);
+        assert!(is_perm(&g));
+        g.infect(&winner, 1.0, &(), &mut rng);
+        assert_eq!(g, winner);
+        assert_eq!(g.distance(&Permutation((0..10).collect())), 10.0)
+    }
+
+    #[test]
+    fn test_graph() {
+        let mut rng = thread_rng();
+        let params = GraphParams { symmetric: true, ..GraphParams::default() };
+        let mut g = GraphGenome::random(36, &params, &mut rng);
+        assert_eq!(g.links.len(), 6);
+        g.mutate(0.3, &params, &mut rng);
+        for i in 0..6 {
+            assert_eq!(g.links[i][i], 0);
+            for j in 0..6 {
+                assert_eq!(g.links[i][j], g.links[j][i]);
+            }
+        }
+
+        let empty = GraphGenome { links: vec![vec![0; 6]; 6] };
+        let edges: usize = g.links.iter().flatten().map(|l| *l as usize).sum();
+        assert_eq!(g.distance(&empty), edges as f64);
+        g.infect(&empty, 1.0, &params, &mut rng);
+        assert_eq!(g, empty)
+    }
+}
@@ -1,17 +1,104 @@
-use crate::vmath::VMath;
-use crate::vmath::EMath;
-use crate::vmath;
+use crate::attractor::{self, Attractor, ClassifyParams};
+use crate::forcing::Forcing;
+use crate::immigration::Immigration;
+use crate::integrate::{self, Dynamics};
+use crate::trajectory::{Record, Trajectory};
 use crate::utils;
+use crate::vmath;
 use crate::glv;
-use rand_distr::Exp1;
+use crate::coeffs::{CoeffSpec, Effect, SignedTemplate};
 use rand::prelude::*;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct GLV {
     pub n: usize,  // Number of species
     pub x: Vec<f64>,  // Density of each species
     pub r: Vec<f64>,  // Intrinsic growth rates
     pub a: Vec<Vec<f64>>,  // Competiton matrix
+    pub kernel: Kernel,  // Functional response on each link
+    pub immigration: Immigration,  // Arrivals from outside, closed by default
+    pub r_forcing: Forcing,  // Time varying factor on each r_i
+    pub a_forcing: Forcing,  // Time varying factor on the off diagonal of row i
+    pub harvest: Vec<f64>,  // Constant per capita harvesting effort
+}
+
+// how the density of species j enters the per capita growth of species i,
+// ie the f in x_i' = r_i x_i (1 - sum_j a_ij f_ij(x_j)). self limitation on
+// the diagonal is always linear
+#[derive(Debug, Clone, PartialEq)]
+pub enum Kernel {
+    Linear,
+    HollingII(Vec<Vec<f64>>),  // x / (1 + h_ij x) with handling times h_ij
+    HollingIII(Vec<Vec<f64>>),  // x^2 / (1 + h_ij x^2)
+}
+
+impl Kernel {
+    // the same handling time on every link
+    pub fn uniform_holling_ii(n: usize, h: f64) -> Kernel {
+        return Kernel::HollingII(Kernel::off_diagonal(n, h))
+    }
+
+    pub fn uniform_holling_iii(n: usize, h: f64) -> Kernel {
+        return Kernel::HollingIII(Kernel::off_diagonal(n, h))
+    }
+
+    pub fn response(&self, i: usize, j: usize, xj: f64) -> f64 {
+        if i == j {
+            return xj
+        }
+        match self {
+            Kernel::Linear => xj,
+            Kernel::HollingII(h) => xj / (1.0 + h[i][j] * xj),
+            Kernel::HollingIII(h) => {
+                let x2 = xj * xj;
+                x2 / (1.0 + h[i][j] * x2)
+            },
+        }
+    }
+
+    // d response / d xj, for the jacobian and the sensitivities
+    pub fn slope(&self, i: usize, j: usize, xj: f64) -> f64 {
+        if i == j {
+            return 1.0
+        }
+        match self {
+            Kernel::Linear => 1.0,
+            Kernel::HollingII(h) => {
+                let d = 1.0 + h[i][j] * xj;
+                1.0 / (d * d)
+            },
+            Kernel::HollingIII(h) => {
+                let d = 1.0 + h[i][j] * xj * xj;
+                2.0 * xj / (d * d)
+            },
+        }
+    }
+
+    pub fn subset(&self, idx: &Vec<usize>) -> Kernel {
+        match self {
+            Kernel::Linear => Kernel::Linear,
+            Kernel::HollingII(h) => Kernel::HollingII(utils::submatrix(h, idx)),
+            Kernel::HollingIII(h) => Kernel::HollingIII(utils::submatrix(h, idx)),
+        }
+    }
+
+    fn off_diagonal(n: usize, h: f64) -> Vec<Vec<f64>> {
+        let mut out = vec![vec![h; n]; n];
+        for i in 0..n {
+            out[i][i] = 0.0;
+        }
+        return out
+    }
+}
+
+// one run of a batch, indices point back into the inputs of simulate_batch,
+// start is into the starts of that matrix
+#[derive(Debug, Clone)]
+pub struct BatchRun {
+    pub matrix: usize,
+    pub start: usize,
+    pub trajectory: Trajectory,
+    pub attractor: Option<Attractor>,  // None unless the batch was asked to classify
 }
 
 impl GLV {
@@ -21,6 +108,11 @@ impl GLV {
             x: vec![0.0; n_species],
             r: vec![1.0; n_species],
             a: vec![vec![0.0; n_species]; n_species],
+            kernel: Kernel::Linear,
+            immigration: Immigration::Closed,
+            r_forcing: Forcing::Constant,
+            a_forcing: Forcing::Constant,
+            harvest: vec![0.0; n_species],
         };
         // fill diagonal with ones
         for i in 0..n_species {
@@ -31,69 +123,193 @@ impl GLV {
     }
 
     pub fn step(&self, dt: f64) -> Vec<f64> {
-        let interactions = match self.a.dot(&self.x) {
-            vmath::DotResult::Vector(o) => o,
-            _ => vec![0.0; self.n]
-        };
-        let one_minus = match vec![1.0; self.n].esub(&interactions) {
-            vmath::ElemResult::Vector(o) => o,
-            _ => vec![0.0; self.n]
-        };
-        let rx = match self.r.emul(&self.x) {
-            vmath::ElemResult::Vector(o) => o,
-            _ => vec![0.0; self.n]
-        };
-        let f = match rx.emul(&one_minus) {
-            vmath::ElemResult::Vector(o) => o,
-            _ => vec![0.0; self.n]
-        };
-        let update = match f.emul(&vec![dt; self.n]) {
-            vmath::ElemResult::Vector(o) => o,
-            _ => vec![0.0; self.n]
-        };
-        let new_vals = match self.x.eadd(&update) {
-            vmath::ElemResult::Vector(o) => o,
-            _ => vec![0.0; self.n]
-        };
+        let mut new_vals = vec![0.0; self.n];
+        self.step_into(dt, &mut new_vals);
         return new_vals
     }
 
+    // same update as step but written into a caller owned buffer so the
+    // hot loop in simulate never touches the allocator
+    pub fn step_into(&self, dt: f64, out: &mut Vec<f64>) {
+        assert_eq!(out.len(), self.n);
+        integrate::euler_step(self, 0.0, dt, &self.x, out);
+    }
+
     pub fn randomize_coeffs(&mut self, template: &Vec<Vec<u8>>) {
+        self.randomize_coeffs_with(template, &CoeffSpec::default());
+    }
+
+    pub fn randomize_coeffs_with(&mut self, template: &Vec<Vec<u8>>, spec: &CoeffSpec) {
         assert_eq!(self.a.len(), template.len());
         assert_eq!(self.a[0].len(), template[0].len());
+        let mut rng = thread_rng();
         for i in 0..self.a.len() {
             for j in 0..self.a[i].len() {
                 // keeps things sort of grounded
                 if i == j {
-                    self.a[i][j] = 1.0;
+                    self.a[i][j] = spec.sample_diag(&mut rng);
                 // heres our random coefficients along edges
                 } else if template[i][j] == 1 {
-                    self.a[i][j] = thread_rng().sample(Exp1);
+                    self.a[i][j] = spec.sample_edge(&mut rng);
                 // otherwise its all zero
                 } else {
                     self.a[i][j] = 0.0;
                 }
             }
         }
+        if let Some(growth) = &spec.growth {
+            for i in 0..self.n {
+                self.r[i] = growth.sample(&mut rng);
+            }
+        }
+    }
+
+    // like randomize_coeffs_with but the template decides the sign of each
+    // entry, so only the magnitude of the off diagonal draw is used
+    pub fn randomize_coeffs_signed(&mut self, template: &SignedTemplate, spec: &CoeffSpec) {
+        assert_eq!(self.n, template.n);
+        let mut rng = thread_rng();
+        for i in 0..self.n {
+            for j in 0..self.n {
+                if i == j {
+                    self.a[i][j] = spec.sample_diag(&mut rng);
+                    continue;
+                }
+                self.a[i][j] = match template.effects[i][j] {
+                    Effect::Harm => spec.off_diag.sample(&mut rng).abs(),
+                    Effect::Benefit => -spec.off_diag.sample(&mut rng).abs(),
+                    Effect::Absent => 0.0,
+                };
+            }
+        }
+        if let Some(growth) = &spec.growth {
+            for i in 0..self.n {
+                self.r[i] = growth.sample(&mut rng);
+            }
+        }
     }
 
-    pub fn simulate(mut model: glv::GLV, state: Vec<f64>, stop: f64, dt: f64) -> Vec<Vec<f64>> {
-        let times = utils::range(0.0,stop, dt);
-        let mut out_vec: Vec<Vec<f64>> = Vec::new();
-        out_vec.push(state.clone());
+    pub fn simulate(model: glv::GLV, state: Vec<f64>, stop: f64, dt: f64) -> Trajectory {
+        return GLV::simulate_with(model, state, stop, dt, &Record::Full)
+    }
 
-        model.x = state;
+    // when only the end point matters we skip the history entirely
+    pub fn simulate_final(model: glv::GLV, state: Vec<f64>, stop: f64, dt: f64) -> Vec<f64> {
+        let out = GLV::simulate_with(model, state, stop, dt, &Record::Final);
+        return out.data
+    }
 
-        // update the state and get the state vector for this time step
-        for _ in 1..times.len() {
-            let new_x = model.step(dt);
-            out_vec.push(new_x.clone());
-            model.x = new_x;
+    pub fn simulate_with(model: glv::GLV, state: Vec<f64>, stop: f64, dt: f64,
+                         record: &Record) -> Trajectory {
+        return integrate::simulate(&model, state, stop, dt, record)
+    }
+
+    // runs every interaction matrix from each of its own initial states,
+    // starts[m] for matrix m. everything else (n, r, ...) comes from base.
+    // runs are spread over the available cores and come back ordered by
+    // matrix and then by start. with classify every run also gets its
+    // attractor, from the recorded trajectory when it is Full or Every and
+    // from a second full length run otherwise
+    pub fn simulate_batch(base: &GLV, matrices: &Vec<Vec<Vec<f64>>>, starts: &Vec<Vec<Vec<f64>>>,
+                          stop: f64, dt: f64, record: &Record,
+                          classify: Option<&ClassifyParams>) -> Vec<BatchRun> {
+        assert_eq!(matrices.len(), starts.len());
+        let pairs: Vec<(usize, usize)> = (0..matrices.len())
+            .flat_map(|m| (0..starts[m].len()).map(move |s| (m, s)))
+            .collect();
+        let total = pairs.len();
+        if total == 0 {
+            return Vec::new()
         }
+        let threads = match std::thread::available_parallelism() {
+            Ok(n) => n.get().min(total),
+            Err(_) => 1,
+        };
+        let chunk = total.div_ceil(threads);
 
-        return out_vec
+        let run = |k: usize| -> BatchRun {
+            let (m, s) = pairs[k];
+            let mut model = base.clone();
+            model.a = matrices[m].clone();
+            let trajectory = integrate::simulate(&model, starts[m][s].clone(), stop, dt, record);
+            let attractor = match (classify, record) {
+                (None, _) => None,
+                (Some(params), Record::Full) | (Some(params), Record::Every(_)) => {
+                    Some(attractor::classify_recorded(&model, &trajectory, stop, dt, params))
+                },
+                (Some(params), _) => {
+                    Some(attractor::classify(&model, starts[m][s].clone(), stop, dt, params))
+                },
+            };
+            BatchRun {
+                matrix: m,
+                start: s,
+                trajectory: trajectory,
+                attractor: attractor,
+            }
+        };
+
+        let mut out: Vec<BatchRun> = Vec::with_capacity(total);
+        std::thread::scope(|scope| {
+            let mut handles = Vec::with_capacity(threads);
+            for t in 0..threads {
+                let run = &run;
+                let lo = t * chunk;
+                let hi = ((t + 1) * chunk).min(total);
+                handles.push(scope.spawn(move || {
+                    let mut runs: Vec<BatchRun> = Vec::with_capacity(hi.saturating_sub(lo));
+                    for k in lo..hi {
+                        runs.push(run(k));
+                    }
+                    runs
+                }));
+            }
+            for handle in handles {
+                out.extend(handle.join().expect("batch worker panicked"));
+            }
+        });
+        return out
+    }
+
+    // per capita growth rate of species i at state x, r_i (1 - sum_j a_ij f(x_j)),
+    // using the unforced parameters
+    pub fn per_capita_growth(&self, i: usize, x: &[f64]) -> f64 {
+        let mut interactions = 0.0;
+        for j in 0..self.n {
+            interactions += self.a[i][j] * self.kernel.response(i, j, x[j]);
+        }
+        return self.r[i] * (1.0 - interactions)
+    }
+
+    // interior equilibrium of the linear model, ie the x with a x = 1. it is
+    // only meaningful when every entry is positive, see is_feasible. None
+    // for singular a or a nonlinear kernel
+    pub fn equilibrium(&self) -> Option<Vec<f64>> {
+        if self.kernel != Kernel::Linear {
+            return None
+        }
+        return vmath::solve(&self.a, &vec![1.0; self.n])
+    }
+
+    pub fn is_feasible(x: &Vec<f64>) -> bool {
+        return x.iter().all(|xi| *xi > 0.0)
     }
- 
+
+    // the community made of just the listed species, in that order
+    pub fn subsystem(&self, idx: &Vec<usize>) -> GLV {
+        GLV {
+            n: idx.len(),
+            x: idx.iter().map(|i| self.x[*i]).collect(),
+            r: idx.iter().map(|i| self.r[*i]).collect(),
+            a: utils::submatrix(&self.a, idx),
+            kernel: self.kernel.subset(idx),
+            immigration: self.immigration.subset(idx),
+            r_forcing: self.r_forcing.subset(idx),
+            a_forcing: self.a_forcing.subset(idx),
+            harvest: idx.iter().map(|i| self.harvest[*i]).collect(),
+        }
+    }
+
     pub fn vec_to_mat<T: Copy>(v: &Vec<T>, size: usize) -> Vec<Vec<T>> {
         let vv = v.clone();
         let mut m: Vec<Vec<T>> = Vec::with_capacity(size);
@@ -108,11 +324,55 @@ impl GLV {
         }
         return m
     }
+
+    // the right hand side on plain slices, so a caller holding a bigger state
+    // (eg the patches of a metacommunity) can hand over its pieces directly
+    pub fn rates(&self, t: f64, x: &[f64], out: &mut [f64]) {
+        for i in 0..self.n {
+            // self limitation is not forced
+            let a_scale = self.a_forcing.factor(t, i);
+            let mut interactions = 0.0;
+            for j in 0..self.n {
+                let scale = if i == j { 1.0 } else { a_scale };
+                interactions += self.a[i][j] * scale * self.kernel.response(i, j, x[j]);
+            }
+            let r_i = self.r[i] * self.r_forcing.factor(t, i);
+            out[i] = (r_i * x[i]) * (1.0 - interactions);
+            out[i] += self.immigration.flux(i);
+            out[i] -= self.harvest[i] * x[i];
+        }
+    }
+}
+
+impl Dynamics for GLV {
+    fn dim(&self) -> usize {
+        return self.n
+    }
+
+    fn derivative(&self, t: f64, x: &Vec<f64>, out: &mut Vec<f64>) {
+        self.rates(t, x, out);
+    }
+
+    fn after_step(&self, x: &mut Vec<f64>, dt: f64) {
+        // only pay for the rng when there is something to draw
+        if self.stochastic() {
+            self.immigration.arrivals(x, dt, &mut thread_rng());
+        }
+    }
+
+    fn stochastic(&self) -> bool {
+        return matches!(self.immigration, Immigration::Stochastic { .. })
+    }
 }
 
 #[cfg(test)]
 mod test_glv {
-    use super::GLV;
+    use super::{GLV, Kernel};
+    use crate::attractor::{Attractor, ClassifyParams};
+    use crate::forcing::Forcing;
+    use crate::immigration::Immigration;
+    use crate::coeffs::{CoeffSpec, Dist, Interaction, SignedTemplate};
+    use crate::trajectory::Record;
 
     #[test]
     fn test_new() {
@@ -142,6 +402,53 @@ mod test_glv {
         assert_eq!(new_x[2], 0.6009);
     }
 
+    #[test]
+    fn test_step_holling() {
+        let mut glv = GLV::new(2);
+        glv.a[0][1] = 1.0;
+        glv.x = vec![0.5, 1.0];
+
+        glv.kernel = Kernel::uniform_holling_ii(2, 1.0);
+        let type_ii = glv.step(0.1);
+        glv.kernel = Kernel::uniform_holling_iii(2, 1.0);
+        let type_iii = glv.step(0.1);
+
+        // x_0 += 0.1 * 0.5 * (1 - 0.5 - f(1.0)) with f = 1/2 for both kernels
+        assert!((type_ii[0] - 0.5).abs() < 1e-12);
+        assert!((type_iii[0] - 0.5).abs() < 1e-12);
+        // past x = 1 the sigmoid response saturates to a higher level
+        glv.x = vec![0.5, 2.0];
+        let type_iii = glv.step(0.1);
+        glv.kernel = Kernel::uniform_holling_ii(2, 1.0);
+        let type_ii = glv.step(0.1);
+        assert!(type_iii[0] < type_ii[0])
+    }
+
+    #[test]
+    fn test_immigration() {
+        // 1 excludes 0 in a closed community
+        let mut glv = GLV::new(2);
+        glv.a[0][1] = 2.0;
+        let closed = GLV::simulate_final(glv.clone(), vec![0.1, 0.5], 50.0, 0.01);
+
+        glv.immigration = Immigration::Constant(vec![0.01, 0.0]);
+        let open = GLV::simulate_final(glv, vec![0.1, 0.5], 50.0, 0.01);
+
+        assert!(closed[0] < 1e-3);
+        assert!(open[0] > 0.005)
+    }
+
+    #[test]
+    fn test_forced_growth() {
+        let mut glv = GLV::new(1);
+        glv.r_forcing = Forcing::Piecewise { times: vec![0.5], factors: vec![vec![0.0]] };
+
+        // growth switches off half way through
+        let out = GLV::simulate_with(glv, vec![0.1], 1.0, 0.01, &Record::Full);
+        assert!(out[50][0] > out[10][0]);
+        assert_eq!(out[51][0], out[99][0])
+    }
+
     #[test]
     fn test_randomize_coeffs() {
         let mut glv = GLV::new(3);
@@ -156,6 +463,43 @@ mod test_glv {
         assert_eq!(glv.a[2][1], 0.0)
     }
 
+    #[test]
+    fn test_randomize_coeffs_with() {
+        let mut glv = GLV::new(2);
+        let graph = vec![vec![0, 1],
+                         vec![0, 0]];
+        let spec = CoeffSpec {
+            diag: Dist::Uniform(0.5, 1.0),
+            off_diag: Dist::Fixed(0.2),
+            p_negative: 0.0,
+            growth: Some(Dist::Fixed(2.0)),
+        };
+
+        glv.randomize_coeffs_with(&graph, &spec);
+
+        assert!((glv.a[0][0] >= 0.5) & (glv.a[0][0] < 1.0));
+        assert_eq!(glv.a[0][1], 0.2);
+        assert_eq!(glv.a[1][0], 0.0);
+        assert_eq!(glv.r, vec![2.0, 2.0])
+    }
+
+    #[test]
+    fn test_randomize_coeffs_signed() {
+        let mut glv = GLV::new(3);
+        let mut template = SignedTemplate::new(3);
+        template.set(0, 1, Interaction::Predation);
+        template.set(1, 2, Interaction::Commensalism);
+
+        glv.randomize_coeffs_signed(&template, &CoeffSpec::default());
+
+        assert!(glv.a[0][1] < 0.0);
+        assert!(glv.a[1][0] > 0.0);
+        assert!(glv.a[2][1] < 0.0);
+        assert_eq!(glv.a[1][2], 0.0);
+        assert_eq!(glv.a[0][2], 0.0);
+        assert_eq!(glv.a[0][0], 1.0)
+    }
+
     #[test]
     fn test_simulate() {
         let mut glv = GLV::new(3);
@@ -174,6 +518,102 @@ mod test_glv {
         assert!((out[99][2] > 0.0) & (out[99][2] < 1.0))
     }
 
+    #[test]
+    fn test_simulate_final() {
+        let mut glv = GLV::new(3);
+        glv.a[0][1] = 0.5;
+        glv.a[1][2] = 0.5;
+        glv.a[2][0] = 0.5;
+
+        let mut full_glv = GLV::new(3);
+        full_glv.a = glv.a.clone();
+
+        let out = GLV::simulate(full_glv, vec![0.5, 0.45, 0.55], 1.0, 0.001);
+        let end = GLV::simulate_final(glv, vec![0.5, 0.45, 0.55], 1.0, 0.001);
+
+        assert_eq!(out.last(), Some(&end[..]))
+    }
+
+    #[test]
+    fn test_simulate_with_every() {
+        let glv = GLV::new(2);
+        let out = GLV::simulate_with(glv, vec![0.5, 0.5], 1.0, 0.01, &Record::Every(10));
+
+        assert_eq!(out.len(), 11);
+        assert!((out.t[1] - 0.1).abs() < 1e-12);
+        assert!((out.t[10] - 0.99).abs() < 1e-12)
+    }
+
+    #[test]
+    fn test_simulate_batch() {
+        let base = GLV::new(2);
+        let mut weak = GLV::new(2).a;
+        weak[0][1] = 0.5;
+        let matrices = vec![base.a.clone(), weak.clone()];
+        let starts = vec![vec![vec![0.1, 0.2], vec![0.3, 0.4], vec![0.5, 0.6]],
+                          vec![vec![0.7, 0.8], vec![0.9, 0.1], vec![0.2, 0.3]]];
+
+        let runs = GLV::simulate_batch(&base, &matrices, &starts, 1.0, 0.01, &Record::Final, None);
+
+        assert_eq!(runs.len(), 6);
+        assert_eq!((runs[4].matrix, runs[4].start), (1, 1));
+        let mut single = GLV::new(2);
+        single.a = weak;
+        let end = GLV::simulate_final(single, vec![0.9, 0.1], 1.0, 0.01);
+        assert_eq!(runs[4].trajectory.data, end);
+        assert!(runs[4].attractor.is_none());
+
+        let params = ClassifyParams::default();
+        let runs = GLV::simulate_batch(&base, &matrices, &starts, 30.0, 0.01, &Record::Every(10), Some(&params));
+        assert!(matches!(runs[0].attractor, Some(Attractor::FixedPoint { .. })));
+        let runs = GLV::simulate_batch(&base, &matrices, &starts, 30.0, 0.01, &Record::Final, Some(&params));
+        assert_eq!(runs[0].trajectory.len(), 1);
+        assert!(matches!(runs[0].attractor, Some(Attractor::FixedPoint { .. })))
+    }
+
+    #[test]
+    fn test_simulate_batch_chaos() {
+        // Vano et al. (2006) chaotic 4 species competition, only the
+        // lyapunov fallback can tell it apart from an unsettled transient
+        let mut base = GLV::new(4);
+        base.r = vec![1.0, 0.72, 1.53, 1.27];
+        let matrices = vec![vec![vec![1.0, 1.09, 1.52, 0.0],
+                                 vec![0.0, 1.0, 0.44, 1.36],
+                                 vec![2.33, 0.0, 1.0, 0.47],
+                                 vec![1.21, 0.51, 0.35, 1.0]]];
+        let starts = vec![vec![vec![0.3, 0.3, 0.3, 0.3]]];
+        let params = ClassifyParams::default();
+
+        for record in [Record::Every(10), Record::Final].iter() {
+            let runs = GLV::simulate_batch(&base, &matrices, &starts, 400.0, 0.01, record, Some(&params));
+            assert!(matches!(runs[0].attractor, Some(Attractor::Chaotic { .. })));
+        }
+    }
+
+    #[test]
+    fn test_subsystem() {
+        let mut glv = GLV::new(3);
+        glv.a[2][0] = 0.7;
+        glv.r = vec![1.0, 2.0, 3.0];
+        let sub = glv.subsystem(&vec![2, 0]);
+
+        assert_eq!(sub.n, 2);
+        assert_eq!(sub.r, vec![3.0, 1.0]);
+        assert_eq!(sub.a, vec![vec![1.0, 0.7], vec![0.0, 1.0]])
+    }
+
+    #[test]
+    fn test_equilibrium() {
+        let mut glv = GLV::new(2);
+        glv.a[0][1] = 0.5;
+        glv.a[1][0] = 0.5;
+        let x = glv.equilibrium().unwrap();
+
+        assert!(GLV::is_feasible(&x));
+        assert!((x[0] - 2.0 / 3.0).abs() < 1e-12);
+        assert!(glv.per_capita_growth(1, &x).abs() < 1e-12)
+    }
+
     #[test]
     fn test_vec_to_mat() {
         let in_vec = vec![1, 2, 3, 4, 5, 6, 7, 8, 9];
@@ -0,0 +1,189 @@
+use crate::glv::GLV;
+use crate::integrate::{self, Dynamics};
+
+// derivatives of the state at time t with respect to every r_i and a_ij
+#[derive(Debug, Clone)]
+pub struct Sensitivities {
+    pub t: f64,
+    pub state: Vec<f64>,
+    pub dr: Vec<Vec<f64>>,  // dr[k][i] = d x_k / d r_i
+    pub da: Vec<Vec<Vec<f64>>>,  // da[k][i][j] = d x_k / d a_ij
+}
+
+impl Sensitivities {
+    fn zero(n: usize, state: Vec<f64>) -> Sensitivities {
+        Sensitivities {
+            t: 0.0,
+            state: state,
+            dr: vec![vec![0.0; n]; n],
+            da: vec![vec![vec![0.0; n]; n]; n],
+        }
+    }
+}
+
+// the pieces of the GLV right hand side at (t, x) the sensitivities need:
+// the jacobian in x, d f_i / d r_i and d f_i / d a_ij (f_i only depends on
+// its own r_i and row of a)
+fn partials(glv: &GLV, t: f64, x: &Vec<f64>) -> (Vec<Vec<f64>>, Vec<f64>, Vec<Vec<f64>>) {
+    let n = glv.n;
+    let mut jac = vec![vec![0.0; n]; n];
+    let mut df_dr = vec![0.0; n];
+    let mut df_da = vec![vec![0.0; n]; n];
+    for i in 0..n {
+        let a_scale = glv.a_forcing.factor(t, i);
+        let r_factor = glv.r_forcing.factor(t, i);
+        let r_i = glv.r[i] * r_factor;
+        let mut interactions = 0.0;
+        for j in 0..n {
+            let scale = if i == j { 1.0 } else { a_scale };
+            let resp = glv.kernel.response(i, j, x[j]);
+            interactions += glv.a[i][j] * scale * resp;
+            df_da[i][j] = -r_i * x[i] * scale * resp;
+            jac[i][j] = -r_i * x[i] * glv.a[i][j] * scale * glv.kernel.slope(i, j, x[j]);
+        }
+        jac[i][i] += r_i * (1.0 - interactions) - glv.harvest[i];
+        df_dr[i] = r_factor * x[i] * (1.0 - interactions);
+    }
+    return (jac, df_dr, df_da)
+}
+
+// forward sensitivities of the euler scheme itself, so they are the exact
+// derivatives of what integrate::simulate produces:
+// S_{k+1} = S_k + dt (J_k S_k + df/dp). states are kept at the first step at
+// or after each of times, like Record::Times. random arrivals would move the
+// state without moving the sensitivities, so stochastic immigration is rejected
+pub fn sensitivities_at(glv: &GLV, state: Vec<f64>, stop: f64, dt: f64, times: &Vec<f64>) -> Vec<Sensitivities> {
+    let n = glv.n;
+    assert_eq!(state.len(), n);
+    assert!(!glv.stochastic(), "sensitivities of a stochastic model");
+    let steps = integrate::steps(stop, dt);
+    let mut out: Vec<Sensitivities> = Vec::with_capacity(times.len());
+    let mut s = Sensitivities::zero(n, state);
+    let mut next = vec![0.0; n];
+    let mut next_time = 0;
+
+    let mut keep = |s: &Sensitivities, t: f64, next_time: &mut usize| {
+        while (*next_time < times.len()) && (times[*next_time] <= t) {
+            let mut kept = s.clone();
+            kept.t = t;
+            out.push(kept);
+            *next_time += 1;
+        }
+    };
+    keep(&s, 0.0, &mut next_time);
+
+    for step in 1..steps {
+        let t = ((step - 1) as f64) * dt;
+        let (jac, df_dr, df_da) = partials(glv, t, &s.state);
+
+        let mut dr = s.dr.clone();
+        let mut da = s.da.clone();
+        for k in 0..n {
+            for l in 0..n {
+                if jac[k][l] == 0.0 {
+                    continue;
+                }
+                let w = dt * jac[k][l];
+                for i in 0..n {
+                    dr[k][i] += w * s.dr[l][i];
+                    for j in 0..n {
+                        da[k][i][j] += w * s.da[l][i][j];
+                    }
+                }
+            }
+            dr[k][k] += dt * df_dr[k];
+            for j in 0..n {
+                da[k][k][j] += dt * df_da[k][j];
+            }
+        }
+
+        integrate::euler_step(glv, t, dt, &s.state, &mut next);
+        std::mem::swap(&mut s.state, &mut next);
+        s.dr = dr;
+        s.da = da;
+        keep(&s, (step as f64) * dt, &mut next_time);
+    }
+    return out
+}
+
+// sensitivities of the state simulate ends on
+pub fn sensitivities(glv: &GLV, state: Vec<f64>, stop: f64, dt: f64) -> Sensitivities {
+    let last = integrate::steps(stop, dt).max(1) - 1;
+    let end = (last as f64) * dt;
+    return sensitivities_at(glv, state, stop, dt, &vec![end - 0.5 * dt]).pop().unwrap()
+}
+
+#[cfg(test)]
+mod test_gradients {
+    use super::*;
+    use crate::forcing::Forcing;
+    use crate::glv::Kernel;
+    use crate::immigration::Immigration;
+
+    fn model() -> GLV {
+        let mut glv = GLV::new(3);
+        glv.r = vec![1.0, 0.8, 1.2];
+        glv.a = vec![vec![1.0, 0.5, 0.3],
+                     vec![0.2, 1.0, 0.6],
+                     vec![0.4, -0.3, 1.0]];
+        glv.kernel = Kernel::uniform_holling_ii(3, 0.5);
+        glv.r_forcing = Forcing::seasonal(3, 0.2, 3.0);
+        glv.harvest = vec![0.0, 0.05, 0.0];
+        glv
+    }
+
+    #[test]
+    fn test_against_finite_differences() {
+        let glv = model();
+        let start = vec![0.2, 0.5, 0.3];
+        let sens = sensitivities(&glv, start.clone(), 5.0, 0.01);
+        let end = GLV::simulate_final(glv.clone(), start.clone(), 5.0, 0.01);
+        for k in 0..3 {
+            assert!((sens.state[k] - end[k]).abs() < 1e-12);
+        }
+
+        let h = 1e-6;
+        for i in 0..3 {
+            let mut up = glv.clone();
+            let mut down = glv.clone();
+            up.r[i] += h;
+            down.r[i] -= h;
+            let x_up = GLV::simulate_final(up, start.clone(), 5.0, 0.01);
+            let x_down = GLV::simulate_final(down, start.clone(), 5.0, 0.01);
+            for k in 0..3 {
+                assert!((sens.dr[k][i] - (x_up[k] - x_down[k]) / (2.0 * h)).abs() < 1e-6);
+            }
+
+            for j in 0..3 {
+                let mut up = glv.clone();
+                let mut down = glv.clone();
+                up.a[i][j] += h;
+                down.a[i][j] -= h;
+                let x_up = GLV::simulate_final(up, start.clone(), 5.0, 0.01);
+                let x_down = GLV::simulate_final(down, start.clone(), 5.0, 0.01);
+                for k in 0..3 {
+                    assert!((sens.da[k][i][j] - (x_up[k] - x_down[k]) / (2.0 * h)).abs() < 1e-6);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_times() {
+        let glv = model();
+        let at = sensitivities_at(&glv, vec![0.2, 0.5, 0.3], 5.0, 0.01, &vec![0.0, 1.0, 1.0, 2.5]);
+
+        assert_eq!(at.len(), 4);
+        assert_eq!(at[0].dr[0][0], 0.0);
+        assert_eq!(at[1].state, at[2].state);
+        assert!(at[3].t >= 2.5)
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_rejects_stochastic() {
+        let mut glv = model();
+        glv.immigration = Immigration::Stochastic { rates: vec![0.1; 3], propagule: 0.01 };
+        sensitivities(&glv, vec![0.2, 0.5, 0.3], 1.0, 0.01);
+    }
+}
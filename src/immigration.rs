@@ -0,0 +1,94 @@
+use rand::Rng;
+
+// how individuals arrive from outside the community
+#[derive(Debug, Clone, PartialEq)]
+pub enum Immigration {
+    Closed,
+    Constant(Vec<f64>),  // density added per unit time, x_i' += m_i
+    Stochastic { rates: Vec<f64>, propagule: f64 },  // poisson arrivals of a fixed propagule
+}
+
+impl Immigration {
+    // deterministic part, added to the derivative
+    pub fn flux(&self, i: usize) -> f64 {
+        match self {
+            Immigration::Constant(m) => m[i],
+            _ => 0.0,
+        }
+    }
+
+    pub fn subset(&self, idx: &Vec<usize>) -> Immigration {
+        match self {
+            Immigration::Closed => Immigration::Closed,
+            Immigration::Constant(m) => Immigration::Constant(idx.iter().map(|i| m[*i]).collect()),
+            Immigration::Stochastic { rates, propagule } => Immigration::Stochastic {
+                rates: idx.iter().map(|i| rates[*i]).collect(),
+                propagule: *propagule,
+            },
+        }
+    }
+
+    // stochastic part, applied once per step of length dt
+    pub fn arrivals<R: Rng>(&self, x: &mut [f64], dt: f64, rng: &mut R) {
+        if let Immigration::Stochastic { rates, propagule } = self {
+            for i in 0..x.len() {
+                let p_arrive = 1.0 - (-rates[i] * dt).exp();
+                if rng.gen::<f64>() < p_arrive {
+                    x[i] += propagule;
+                }
+            }
+        }
+    }
+}
+
+// relative abundances in the regional species pool that feeds the community
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegionalPool {
+    pub weights: Vec<f64>,  // Sums to one
+}
+
+impl RegionalPool {
+    pub fn new(abundances: &Vec<f64>) -> RegionalPool {
+        let total: f64 = abundances.iter().sum();
+        assert!(total > 0.0, "regional pool needs some abundance");
+        RegionalPool {
+            weights: abundances.iter().map(|a| a / total).collect(),
+        }
+    }
+
+    pub fn uniform(n_species: usize) -> RegionalPool {
+        return RegionalPool::new(&vec![1.0; n_species])
+    }
+
+    // total_rate is split over species by their share of the pool
+    pub fn constant(&self, total_rate: f64) -> Immigration {
+        return Immigration::Constant(self.weights.iter().map(|w| w * total_rate).collect())
+    }
+
+    pub fn stochastic(&self, total_rate: f64, propagule: f64) -> Immigration {
+        Immigration::Stochastic {
+            rates: self.weights.iter().map(|w| w * total_rate).collect(),
+            propagule: propagule,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_immigration {
+    use super::*;
+
+    #[test]
+    fn test_pool_split() {
+        let pool = RegionalPool::new(&vec![1.0, 3.0]);
+        assert_eq!(pool.constant(2.0), Immigration::Constant(vec![0.5, 1.5]))
+    }
+
+    #[test]
+    fn test_arrivals() {
+        let mut x = vec![0.0, 0.0];
+        let imm = Immigration::Stochastic { rates: vec![0.0, 1e9], propagule: 0.01 };
+        imm.arrivals(&mut x, 0.01, &mut rand::thread_rng());
+
+        assert_eq!(x, vec![0.0, 0.01])
+    }
+}
@@ -0,0 +1,387 @@
+use crate::glv::GLV;
+use crate::gradients;
+use crate::integrate;
+use crate::trajectory::{Record, Trajectory};
+use crate::vmath;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Error, ErrorKind, Write};
+
+// knobs for fitting a GLV to time series
+#[derive(Debug, Clone)]
+pub struct InferenceParams {
+    pub ridge: f64,  // penalty on the interaction terms of the regression
+    pub min_abundance: f64,  // intervals where a species is below this are skipped
+    pub refine_iters: usize,  // levenberg-marquardt steps on the trajectories, 0 for none
+    pub dt: f64,  // integration step when comparing trajectories
+}
+
+impl Default for InferenceParams {
+    fn default() -> InferenceParams {
+        InferenceParams {
+            ridge: 1e-4,
+            min_abundance: 1e-8,
+            refine_iters: 0,
+            dt: 0.01,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Fit {
+    pub glv: GLV,  // the estimated r and a
+    pub r_squared: Vec<f64>,  // per species, of the log growth regression
+    pub samples: Vec<usize>,  // intervals each species was fitted on
+    pub rmse: f64,  // simulated against observed abundances over every series
+}
+
+// rows of time followed by one abundance per species, which is what
+// simulate gives back. a header line and trailing commas are fine
+pub fn read_csv<R: BufRead>(input: R) -> Result<Trajectory, Error> {
+    let mut traj: Option<Trajectory> = None;
+    for (k, line) in input.lines().enumerate() {
+        let line = line?;
+        let fields: Vec<&str> = line.trim().trim_end_matches(',').split(',').map(|f| f.trim()).collect();
+        if fields.len() < 2 {
+            continue;
+        }
+        if (k == 0) && fields[0].parse::<f64>().is_err() {
+            continue;
+        }
+        let mut row: Vec<f64> = Vec::with_capacity(fields.len());
+        for f in fields.iter() {
+            match f.parse::<f64>() {
+                Ok(v) => row.push(v),
+                Err(_) => return Err(Error::new(ErrorKind::InvalidData, format!("line {}: bad number {:?}", k + 1, f))),
+            }
+        }
+        let t = traj.get_or_insert_with(|| Trajectory::with_capacity(row.len() - 1, 0));
+        if row.len() - 1 != t.n {
+            return Err(Error::new(ErrorKind::InvalidData, format!("line {}: expected {} species", k + 1, t.n)));
+        }
+        let state = row.split_off(1);
+        t.push(row[0], &state);
+    }
+    return traj.ok_or_else(|| Error::new(ErrorKind::InvalidData, "no data rows"))
+}
+
+pub fn read_csv_file(path: &str) -> Result<Trajectory, Error> {
+    return read_csv(BufReader::new(File::open(path)?))
+}
+
+pub fn write_csv<W: Write>(out: &mut W, traj: &Trajectory) -> Result<(), Error> {
+    write!(out, "time")?;
+    for i in 0..traj.n {
+        write!(out, ",x{}", i)?;
+    }
+    writeln!(out)?;
+    for k in 0..traj.len() {
+        write!(out, "{}", traj.t[k])?;
+        for x in traj[k].iter() {
+            write!(out, ",{}", x)?;
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+// gradient matching. over each interval the log growth of species i is
+// (ln x_i(t+h) - ln x_i(t)) / h ~ r_i - sum_j r_i a_ij x_j at the midpoint,
+// a linear regression on [1, x] with a ridge penalty on the slopes. then
+// r_i is the intercept and a_ij = -slope_j / r_i. an error when there is no
+// data or the regression of some species is singular, eg because it never
+// got above min_abundance, rather than handing back made up values for it
+pub fn gradient_matching(series: &[Trajectory], params: &InferenceParams) -> Result<Fit, Error> {
+    if series.is_empty() {
+        return Err(Error::new(ErrorKind::InvalidInput, "no series to fit"))
+    }
+    let n = series[0].n;
+    let mut glv = GLV::new(n);
+    let mut r_squared = vec![0.0; n];
+    let mut samples = vec![0; n];
+
+    for i in 0..n {
+        let mut xtx = vec![vec![0.0; n + 1]; n + 1];
+        let mut xty = vec![0.0; n + 1];
+        let mut rows: Vec<(Vec<f64>, f64)> = Vec::new();
+        for traj in series.iter() {
+            for k in 1..traj.len() {
+                let (before, after) = (&traj[k - 1], &traj[k]);
+                let h = traj.t[k] - traj.t[k - 1];
+                if (h <= 0.0) || (before[i] < params.min_abundance) || (after[i] < params.min_abundance) {
+                    continue;
+                }
+                let mut row = vec![1.0; n + 1];
+                for j in 0..n {
+                    row[j + 1] = 0.5 * (before[j] + after[j]);
+                }
+                let g = (after[i].ln() - before[i].ln()) / h;
+                for p in 0..(n + 1) {
+                    xty[p] += row[p] * g;
+                    for q in 0..(n + 1) {
+                        xtx[p][q] += row[p] * row[q];
+                    }
+                }
+                rows.push((row, g));
+            }
+        }
+        samples[i] = rows.len();
+        for p in 1..(n + 1) {
+            xtx[p][p] += params.ridge;
+        }
+        let beta = match vmath::solve(&xtx, &xty) {
+            Some(b) if !rows.is_empty() => b,
+            _ => return Err(Error::new(ErrorKind::InvalidData,
+                                       format!("species {}: singular regression on {} intervals", i, rows.len()))),
+        };
+
+        glv.r[i] = beta[0];
+        for j in 0..n {
+            glv.a[i][j] = if beta[0] != 0.0 { -beta[j + 1] / beta[0] } else { 0.0 };
+        }
+
+        let mean = rows.iter().map(|(_, g)| g).sum::<f64>() / (rows.len() as f64);
+        let mut ss_res = 0.0;
+        let mut ss_tot = 0.0;
+        for (row, g) in rows.iter() {
+            let pred: f64 = (0..(n + 1)).map(|p| row[p] * beta[p]).sum();
+            ss_res += (g - pred) * (g - pred);
+            ss_tot += (g - mean) * (g - mean);
+        }
+        r_squared[i] = if ss_tot > 0.0 { 1.0 - ss_res / ss_tot } else { 1.0 };
+    }
+
+    let rmse = trajectory_rmse(&glv, series, params.dt);
+    Ok(Fit {
+        glv: glv,
+        r_squared: r_squared,
+        samples: samples,
+        rmse: rmse,
+    })
+}
+
+// observed times relative to the first row, nudged back half a step so
+// they land on the right step, and a stop time that covers all of them
+fn sample_times(traj: &Trajectory, dt: f64) -> (Vec<f64>, f64) {
+    let t0 = traj.t[0];
+    let times: Vec<f64> = traj.t.iter().map(|t| t - t0 - 0.5 * dt).collect();
+    // the step count floors stop to a whole number, so round up past the end
+    let stop = (traj.t[traj.len() - 1] - t0 + dt).ceil();
+    return (times, stop)
+}
+
+// simulated minus observed at every observed time of every series, each
+// series run from its first row
+fn residuals(glv: &GLV, series: &[Trajectory], dt: f64) -> Vec<f64> {
+    let mut out: Vec<f64> = Vec::new();
+    for traj in series.iter() {
+        let (times, stop) = sample_times(traj, dt);
+        let sim = integrate::simulate(glv, traj[0].to_vec(), stop, dt, &Record::Times(times));
+        for k in 0..traj.len() {
+            for i in 0..traj.n {
+                let s = if k < sim.len() { sim[k][i] } else { f64::NAN };
+                out.push(s - traj[k][i]);
+            }
+        }
+    }
+    return out
+}
+
+fn trajectory_rmse(glv: &GLV, series: &[Trajectory], dt: f64) -> f64 {
+    let res = residuals(glv, series, dt);
+    let sse: f64 = res.iter().map(|e| e * e).sum();
+    return (sse / (res.len() as f64)).sqrt()
+}
+
+// jac[q][k] = d res_k / d theta_q, with theta laid out like pack
+fn residual_jacobian(glv: &GLV, series: &[Trajectory], dt: f64) -> Vec<Vec<f64>> {
+    let n = glv.n;
+    let mut jac: Vec<Vec<f64>> = vec![Vec::new(); n + n * n];
+    for traj in series.iter() {
+        let (times, stop) = sample_times(traj, dt);
+        let sens = gradients::sensitivities_at(glv, traj[0].to_vec(), stop, dt, &times);
+        for k in 0..traj.len() {
+            for l in 0..n {
+                for i in 0..n {
+                    jac[i].push(sens[k].dr[l][i]);
+                    for j in 0..n {
+                        jac[n + i * n + j].push(sens[k].da[l][i][j]);
+                    }
+                }
+            }
+        }
+    }
+    return jac
+}
+
+fn pack(glv: &GLV) -> Vec<f64> {
+    let mut theta = glv.r.clone();
+    for row in glv.a.iter() {
+        theta.extend_from_slice(row);
+    }
+    return theta
+}
+
+fn unpack(glv: &mut GLV, theta: &Vec<f64>) {
+    let n = glv.n;
+    glv.r.copy_from_slice(&theta[0..n]);
+    for i in 0..n {
+        glv.a[i].copy_from_slice(&theta[(n + i * n)..(n + (i + 1) * n)]);
+    }
+}
+
+// levenberg-marquardt on the squared error between simulated and observed
+// trajectories, starting from fit. the jacobian of the residuals comes from
+// the forward sensitivities, in the same order as pack
+pub fn refine(fit: &Fit, series: &[Trajectory], params: &InferenceParams) -> Fit {
+    let mut glv = fit.glv.clone();
+    let mut theta = pack(&glv);
+    let p = theta.len();
+    let mut res = residuals(&glv, series, params.dt);
+    let mut cost: f64 = res.iter().map(|e| e * e).sum();
+    let mut mu = 1e-3;
+
+    for _ in 0..params.refine_iters {
+        if !cost.is_finite() {
+            break;
+        }
+        let jac = residual_jacobian(&glv, series, params.dt);
+        let mut probe = glv.clone();
+
+        let mut jtj = vec![vec![0.0; p]; p];
+        let mut jtr = vec![0.0; p];
+        for a in 0..p {
+            jtr[a] = -(0..res.len()).map(|k| jac[a][k] * res[k]).sum::<f64>();
+            for b in a..p {
+                let v: f64 = (0..res.len()).map(|k| jac[a][k] * jac[b][k]).sum();
+                jtj[a][b] = v;
+                jtj[b][a] = v;
+            }
+        }
+
+        // shrink the damping after a good step, grow it after a bad one
+        let mut improved = false;
+        for _ in 0..10 {
+            let mut damped = jtj.clone();
+            for a in 0..p {
+                damped[a][a] += mu * jtj[a][a].max(1e-12);
+            }
+            if let Some(delta) = vmath::solve(&damped, &jtr) {
+                let trial: Vec<f64> = (0..p).map(|q| theta[q] + delta[q]).collect();
+                unpack(&mut probe, &trial);
+                let trial_res = residuals(&probe, series, params.dt);
+                let trial_cost: f64 = trial_res.iter().map(|e| e * e).sum();
+                if trial_cost < cost {
+                    theta = trial;
+                    glv = probe.clone();
+                    res = trial_res;
+                    cost = trial_cost;
+                    mu = (mu / 3.0).max(1e-12);
+                    improved = true;
+                    break;
+                }
+            }
+            mu *= 2.0;
+        }
+        if !improved {
+            break;
+        }
+    }
+
+    Fit {
+        rmse: (cost / (res.len() as f64)).sqrt(),
+        glv: glv,
+        r_squared: fit.r_squared.clone(),
+        samples: fit.samples.clone(),
+    }
+}
+
+// gradient matching, followed by trajectory refinement when asked for
+pub fn infer(series: &[Trajectory], params: &InferenceParams) -> Result<Fit, Error> {
+    let fit = gradient_matching(series, params)?;
+    if params.refine_iters == 0 {
+        return Ok(fit)
+    }
+    return Ok(refine(&fit, series, params))
+}
+
+#[cfg(test)]
+mod test_inference {
+    use super::*;
+
+    fn truth() -> GLV {
+        let mut glv = GLV::new(3);
+        glv.r = vec![1.0, 0.7, 1.3];
+        glv.a = vec![vec![1.0, 0.4, 0.2],
+                     vec![0.3, 1.0, 0.5],
+                     vec![0.1, 0.6, 1.0]];
+        glv
+    }
+
+    // the transients are what carry the information, so sample a few starts
+    fn synthetic(glv: &GLV) -> Vec<Trajectory> {
+        let starts = vec![vec![0.05, 0.3, 0.1], vec![0.8, 0.02, 0.4], vec![0.2, 0.9, 0.01]];
+        let mut out = Vec::new();
+        for s in starts {
+            out.push(GLV::simulate_with(glv.clone(), s, 15.0, 0.001, &Record::Every(100)));
+        }
+        out
+    }
+
+    #[test]
+    fn test_csv_round_trip() {
+        let text = "time,x0,x1,\n0,0.5,0.25,\n0.1,0.6,0.2,\n";
+        let traj = read_csv(text.as_bytes()).unwrap();
+        assert_eq!(traj.n, 2);
+        assert_eq!(traj.len(), 2);
+        assert_eq!(traj[1][0], 0.6);
+
+        let mut buf: Vec<u8> = Vec::new();
+        write_csv(&mut buf, &traj).unwrap();
+        let back = read_csv(&buf[..]).unwrap();
+        assert_eq!(back.t, traj.t);
+        assert_eq!(back.data, traj.data);
+
+        assert!(read_csv("0,1,2\n1,2\n".as_bytes()).is_err())
+    }
+
+    #[test]
+    fn test_gradient_matching() {
+        let glv = truth();
+        let fit = gradient_matching(&synthetic(&glv), &InferenceParams { ridge: 1e-8, ..InferenceParams::default() }).unwrap();
+
+        for i in 0..3 {
+            assert!((fit.glv.r[i] - glv.r[i]).abs() < 0.05);
+            assert!(fit.r_squared[i] > 0.99);
+            for j in 0..3 {
+                assert!((fit.glv.a[i][j] - glv.a[i][j]).abs() < 0.05);
+            }
+        }
+        assert!(fit.rmse < 0.01)
+    }
+
+    #[test]
+    fn test_gradient_matching_errors() {
+        let params = InferenceParams::default();
+        assert!(gradient_matching(&[], &params).is_err());
+
+        // species 2 is absent throughout, so there is nothing to fit it on
+        let mut absent = truth();
+        absent.r[2] = 0.0;
+        let series = vec![GLV::simulate_with(absent, vec![0.3, 0.2, 0.0], 5.0, 0.01, &Record::Every(10))];
+        assert!(gradient_matching(&series, &params).is_err())
+    }
+
+    #[test]
+    fn test_refine() {
+        let glv = truth();
+        let series = synthetic(&glv);
+        let params = InferenceParams { dt: 0.001, refine_iters: 5, ..InferenceParams::default() };
+        let mut rough = gradient_matching(&series, &params).unwrap();
+        rough.glv.a[0][1] += 0.2;
+        rough.rmse = trajectory_rmse(&rough.glv, &series, params.dt);
+        let fit = refine(&rough, &series, &params);
+
+        assert!(fit.rmse < 0.1 * rough.rmse);
+        assert!((fit.glv.a[0][1] - glv.a[0][1]).abs() < 0.01)
+    }
+}
@@ -0,0 +1,142 @@
+use crate::trajectory::{Record, Recorder, Trajectory};
+use crate::utils;
+
+// anything the integrators can push forward, ie x' = f(t, x)
+pub trait Dynamics {
+    fn dim(&self) -> usize;
+
+    fn derivative(&self, t: f64, x: &Vec<f64>, out: &mut Vec<f64>);
+
+    // chance to fix up the state after each step, eg clamping to a simplex
+    fn after_step(&self, _x: &mut Vec<f64>, _dt: f64) {}
+
+    // whether after_step draws random numbers, two runs from the same state
+    // then no longer follow the same path
+    fn stochastic(&self) -> bool {
+        return false
+    }
+}
+
+// forward euler from x into out, no allocation
+pub fn euler_step<D: Dynamics>(model: &D, t: f64, dt: f64, x: &Vec<f64>, out: &mut Vec<f64>) {
+    model.derivative(t, x, out);
+    for i in 0..x.len() {
+        out[i] = x[i] + out[i] * dt;
+    }
+    model.after_step(out, dt);
+}
+
+// same step count as the original GLV::simulate, utils::range(0, stop, dt)
+pub fn steps(stop: f64, dt: f64) -> usize {
+    return utils::range(0.0, stop, dt).len()
+}
+
+pub fn simulate<D: Dynamics>(model: &D, state: Vec<f64>, stop: f64, dt: f64,
+                             record: &Record) -> Trajectory {
+    assert_eq!(state.len(), model.dim());
+    let steps = steps(stop, dt);
+    let mut rec = Recorder::new(record, model.dim(), steps);
+    let mut x = state;
+    let mut next = vec![0.0; model.dim()];
+    rec.observe(0, 0.0, &x);
+
+    // update the state and get the state vector for this time step
+    for step in 1..steps {
+        euler_step(model, ((step - 1) as f64) * dt, dt, &x, &mut next);
+        rec.observe(step, (step as f64) * dt, &next);
+        std::mem::swap(&mut x, &mut next);
+    }
+
+    let last = steps.max(1) - 1;
+    return rec.finish(last, (last as f64) * dt, &x)
+}
+
+// central difference jacobian J_ij = d x_i' / d x_j at (t, x)
+pub fn jacobian<D: Dynamics>(model: &D, t: f64, x: &Vec<f64>) -> Vec<Vec<f64>> {
+    let n = model.dim();
+    let mut jac = vec![vec![0.0; n]; n];
+    let mut probe = x.clone();
+    let mut up = vec![0.0; n];
+    let mut down = vec![0.0; n];
+    for j in 0..n {
+        let h = 1e-6 * x[j].abs().max(1.0);
+        probe[j] = x[j] + h;
+        model.derivative(t, &probe, &mut up);
+        probe[j] = x[j] - h;
+        model.derivative(t, &probe, &mut down);
+        probe[j] = x[j];
+        for i in 0..n {
+            jac[i][j] = (up[i] - down[i]) / (2.0 * h);
+        }
+    }
+    return jac
+}
+
+// where a run ended up when we stopped waiting for it to settle
+#[derive(Debug, Clone)]
+pub struct Steady {
+    pub state: Vec<f64>,
+    pub time: f64,
+    pub converged: bool,  // max |x'| dropped below tol before max_time
+}
+
+// integrate until every rate of change is below tol or max_time is reached
+pub fn run_to_steady<D: Dynamics>(model: &D, state: Vec<f64>, dt: f64, tol: f64,
+                                  max_time: f64) -> Steady {
+    assert_eq!(state.len(), model.dim());
+    let mut x = state;
+    let mut next = vec![0.0; model.dim()];
+    let mut t = 0.0;
+    while t < max_time {
+        model.derivative(t, &x, &mut next);
+        if next.iter().all(|f| f.abs() < tol) {
+            return Steady { state: x, time: t, converged: true }
+        }
+        euler_step(model, t, dt, &x, &mut next);
+        std::mem::swap(&mut x, &mut next);
+        t += dt;
+    }
+    return Steady { state: x, time: t, converged: false }
+}
+
+#[cfg(test)]
+mod test_integrate {
+    use super::*;
+
+    // x' = -x
+    struct Decay;
+
+    impl Dynamics for Decay {
+        fn dim(&self) -> usize {
+            1
+        }
+
+        fn derivative(&self, _t: f64, x: &Vec<f64>, out: &mut Vec<f64>) {
+            out[0] = -x[0];
+        }
+    }
+
+    #[test]
+    fn test_simulate_decay() {
+        let out = simulate(&Decay, vec![1.0], 1.0, 0.001, &Record::Final);
+        // 999 euler steps of size 0.001
+        let known = (1.0f64 - 0.001).powi(999);
+        assert!((out[0][0] - known).abs() < 1e-12)
+    }
+
+    #[test]
+    fn test_jacobian() {
+        let jac = jacobian(&Decay, 0.0, &vec![3.0]);
+        assert!((jac[0][0] + 1.0).abs() < 1e-9)
+    }
+
+    #[test]
+    fn test_run_to_steady() {
+        let steady = run_to_steady(&Decay, vec![1.0], 0.01, 1e-6, 100.0);
+        assert!(steady.converged);
+        assert!(steady.state[0] < 1e-6);
+
+        let capped = run_to_steady(&Decay, vec![1.0], 0.01, 1e-6, 1.0);
+        assert!(!capped.converged)
+    }
+}
@@ -0,0 +1,86 @@
+use crate::glv::GLV;
+use crate::integrate;
+
+// how species i does when rare against everyone else at their equilibrium
+#[derive(Debug, Clone)]
+pub struct Invasion {
+    pub species: usize,
+    pub growth_rate: f64,  // r_i (1 - sum_j a_ij x*_j), per capita
+    pub can_invade: bool,
+    pub residents: Vec<f64>,  // x* of the other n - 1, with a 0 for species i
+    pub feasible: bool,  // residents came from a feasible interior equilibrium
+}
+
+// where the residents settle before the invader shows up. we use the
+// interior equilibrium when it is feasible, otherwise we run the resident
+// community to its steady state and take whatever survives there
+fn resident_state(glv: &GLV, invader: usize) -> (Vec<f64>, bool) {
+    let others: Vec<usize> = (0..glv.n).filter(|j| *j != invader).collect();
+    let sub = glv.subsystem(&others);
+
+    let (state, feasible) = match sub.equilibrium() {
+        Some(x) if GLV::is_feasible(&x) => (x, true),
+        _ => {
+            let start = vec![0.5; sub.n];
+            let steady = integrate::run_to_steady(&sub, start, 0.01, 1e-8, 500.0);
+            (steady.state, false)
+        },
+    };
+
+    let mut full = vec![0.0; glv.n];
+    for k in 0..others.len() {
+        full[others[k]] = state[k];
+    }
+    return (full, feasible)
+}
+
+pub fn invasion_growth_rates(glv: &GLV) -> Vec<Invasion> {
+    let mut out: Vec<Invasion> = Vec::with_capacity(glv.n);
+    for i in 0..glv.n {
+        let (residents, feasible) = resident_state(glv, i);
+        let growth_rate = glv.per_capita_growth(i, &residents);
+        out.push(Invasion {
+            species: i,
+            growth_rate: growth_rate,
+            can_invade: growth_rate > 0.0,
+            residents: residents,
+            feasible: feasible,
+        });
+    }
+    return out
+}
+
+// every species can invade the rest when rare
+pub fn mutually_invasible(glv: &GLV) -> bool {
+    return invasion_growth_rates(glv).iter().all(|inv| inv.can_invade)
+}
+
+#[cfg(test)]
+mod test_invasibility {
+    use super::*;
+
+    #[test]
+    fn test_weak_competition_coexists() {
+        let mut glv = GLV::new(2);
+        glv.a[0][1] = 0.5;
+        glv.a[1][0] = 0.5;
+        let invasions = invasion_growth_rates(&glv);
+
+        // the resident sits at 1, so the invader grows at 1 - 0.5
+        assert!((invasions[0].growth_rate - 0.5).abs() < 1e-12);
+        assert!(invasions[0].feasible);
+        assert!(mutually_invasible(&glv))
+    }
+
+    #[test]
+    fn test_exclusion() {
+        let mut glv = GLV::new(2);
+        glv.a[0][1] = 2.0;
+        glv.a[1][0] = 0.5;
+        let invasions = invasion_growth_rates(&glv);
+
+        assert!(!invasions[0].can_invade);
+        assert!(invasions[1].can_invade);
+        assert!(!mutually_invasible(&glv))
+    }
+}
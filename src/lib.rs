@@ -0,0 +1,32 @@
+// the simulation models, analyses and the GA. main.rs drives the GA with the
+// fitness functions in fitness, everything else is here to be built on.
+// the code sticks to explicit indexing and early returns
+#![allow(clippy::needless_return, clippy::needless_range_loop, clippy::ptr_arg)]
+#![allow(clippy::upper_case_acronyms, clippy::redundant_field_names)]
+#![allow(clippy::useless_vec, clippy::manual_range_contains)]
+
+pub mod assembly;
+pub mod attractor;
+pub mod coeffs;
+pub mod continuation;
+pub mod crm;
+pub mod fitness;
+pub mod forcing;
+pub mod genome;
+pub mod glv;
+pub mod gradients;
+pub mod immigration;
+pub mod inference;
+pub mod integrate;
+pub mod invasibility;
+pub mod lyapunov;
+pub mod metacommunity;
+pub mod optimize;
+pub mod protocol;
+pub mod replicator;
+pub mod resilience;
+pub mod sbmga;
+pub mod sensitivity;
+pub mod trajectory;
+pub mod utils;
+pub mod vmath;
@@ -0,0 +1,131 @@
+use crate::integrate::{self, Dynamics};
+
+// knobs for max_lyapunov
+#[derive(Debug, Clone)]
+pub struct LyapunovParams {
+    pub dt: f64,
+    pub transient: f64,  // time to run before measuring, to land on the attractor
+    pub time: f64,  // time to average the stretching over
+    pub renorm_every: usize,  // steps between renormalisations
+    pub d0: f64,  // separation of the two trajectories
+}
+
+impl Default for LyapunovParams {
+    fn default() -> LyapunovParams {
+        LyapunovParams {
+            dt: 0.01,
+            transient: 100.0,
+            time: 500.0,
+            renorm_every: 10,
+            d0: 1e-8,
+        }
+    }
+}
+
+fn distance(x: &Vec<f64>, y: &Vec<f64>) -> f64 {
+    let mut d = 0.0;
+    for i in 0..x.len() {
+        d += (x[i] - y[i]) * (x[i] - y[i]);
+    }
+    return d.sqrt()
+}
+
+// largest lyapunov exponent by the two trajectory (Benettin) method. a
+// shadow trajectory starts d0 away, both are integrated side by side and
+// every renorm_every steps the log stretch is added up and the shadow is
+// pulled back to distance d0 along the current separation. positive means
+// chaos, about zero a limit cycle (or a neutral direction) and negative a
+// stable fixed point. stochastic models are rejected, the two trajectories
+// would get independent kicks and their separation would measure the noise
+pub fn max_lyapunov<D: Dynamics>(model: &D, state: Vec<f64>, params: &LyapunovParams) -> f64 {
+    assert!(!model.stochastic(), "lyapunov exponent of a stochastic model");
+    let n = model.dim();
+    let mut x = state;
+    let mut next = vec![0.0; n];
+    let mut t = 0.0;
+
+    let transient_steps = (params.transient / params.dt) as usize;
+    for _ in 0..transient_steps {
+        integrate::euler_step(model, t, params.dt, &x, &mut next);
+        std::mem::swap(&mut x, &mut next);
+        t += params.dt;
+    }
+
+    // kick the shadow along the first axis that is not pinned at zero, so
+    // we stay inside the invariant subspace the trajectory lives in
+    let mut y = x.clone();
+    let axis = (0..n).find(|i| x[*i] > 0.0).unwrap_or(0);
+    y[axis] += params.d0;
+    let mut next_y = vec![0.0; n];
+
+    let steps = (params.time / params.dt) as usize;
+    let every = params.renorm_every.max(1);
+    let mut log_sum = 0.0;
+    let mut elapsed = 0.0;
+    for step in 1..(steps + 1) {
+        integrate::euler_step(model, t, params.dt, &x, &mut next);
+        integrate::euler_step(model, t, params.dt, &y, &mut next_y);
+        std::mem::swap(&mut x, &mut next);
+        std::mem::swap(&mut y, &mut next_y);
+        t += params.dt;
+
+        if step % every == 0 {
+            let d = distance(&x, &y);
+            if d == 0.0 {
+                // collapsed onto each other, nothing left to measure
+                return f64::NEG_INFINITY
+            }
+            log_sum += (d / params.d0).ln();
+            elapsed += (every as f64) * params.dt;
+            for i in 0..n {
+                y[i] = x[i] + (y[i] - x[i]) * (params.d0 / d);
+            }
+        }
+    }
+    if elapsed == 0.0 {
+        return 0.0
+    }
+    return log_sum / elapsed
+}
+
+#[cfg(test)]
+mod test_lyapunov {
+    use super::*;
+    use crate::glv::GLV;
+    use crate::immigration::Immigration;
+
+    #[test]
+    fn test_fixed_point_negative() {
+        let mut glv = GLV::new(2);
+        glv.a[0][1] = 0.5;
+        glv.a[1][0] = 0.5;
+        let params = LyapunovParams { transient: 20.0, time: 50.0, ..LyapunovParams::default() };
+        let lambda = max_lyapunov(&glv, vec![0.3, 0.6], &params);
+
+        // the slowest direction decays at the resilience, 1/3
+        assert!((lambda + 1.0 / 3.0).abs() < 0.02)
+    }
+
+    #[test]
+    fn test_chaotic_four_species() {
+        // Vano et al. (2006) chaotic 4 species competition
+        let mut glv = GLV::new(4);
+        glv.r = vec![1.0, 0.72, 1.53, 1.27];
+        glv.a = vec![vec![1.0, 1.09, 1.52, 0.0],
+                     vec![0.0, 1.0, 0.44, 1.36],
+                     vec![2.33, 0.0, 1.0, 0.47],
+                     vec![1.21, 0.51, 0.35, 1.0]];
+        let params = LyapunovParams { dt: 0.01, transient: 200.0, time: 2000.0, ..LyapunovParams::default() };
+        let lambda = max_lyapunov(&glv, vec![0.3, 0.3, 0.3, 0.3], &params);
+
+        assert!(lambda > 0.005)
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_rejects_stochastic() {
+        let mut glv = GLV::new(2);
+        glv.immigration = Immigration::Stochastic { rates: vec![0.1, 0.1], propagule: 0.01 };
+        max_lyapunov(&glv, vec![0.3, 0.6], &LyapunovParams::default());
+    }
+}
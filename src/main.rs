@@ -1,15 +1,11 @@
-// internal modules
-mod glv;
-mod sbmga;
-mod utils;
-mod vmath;
+// the GA driver keeps the indexed loops of the original script
+#![allow(clippy::needless_range_loop)]
 
-use glv::GLV;
-use utils::range;
-use sbmga::MGA;
+use ecomodels::fitness::{coexistence_search, robust_coexistence_search, SPECIES};
+use ecomodels::glv::GLV;
+use ecomodels::sbmga::MGA;
 
 // external crates
-use rand::Rng;
 use std::fs::File;
 use std::fs::create_dir;
 use std::io::{Error, Write};
@@ -17,7 +13,7 @@ use std::io::{Error, Write};
 
 fn main() -> Result<(), Error> {
     // GA Constants
-    let species = 20;
+    let species = SPECIES;
     let num_genes = species * species;
     let gapop = 40;
     let deme = 3;
@@ -26,107 +22,59 @@ fn main() -> Result<(), Error> {
     let nov_steps = 500;
     let evosteps = 3000;
     let save_every = gapop * 2;
+    // reward resilient communities instead of just rich ones
+    let robust = false;
 
     // initialize GA
-    let mut mga = MGA::new(coexistence_search, gapop, num_genes, deme, pmutate, pinfect);
+    let fitness: fn(&Vec<u8>) -> f64 = if robust { robust_coexistence_search } else { coexistence_search };
+    let mut mga = MGA::new(fitness, gapop, num_genes, deme, pmutate, pinfect);
 
     // build file structure
     // file for tracking fitness in time
     let iter_file = "4";
     let mut ffit = File::create(format!("data/fitness_nov{}.csv", iter_file))?;
-    write!(ffit, "time,");
+    write!(ffit, "time,")?;
     for sp in 0..gapop {
-        write!(ffit, "{},", sp);
+        write!(ffit, "{},", sp)?;
     }
-    write!(ffit, "\n");
+    writeln!(ffit)?;
     // directory for network structures
     create_dir(format!("data/networks_nov{}", iter_file))?;
 
     // Novelty Search (start at time -1 i guess)
-    write!(ffit, "{},", -1);
+    write!(ffit, "{},", -1)?;
     let novfit = mga.evolve_novelty(nov_steps);
     for j in 0..gapop {
-        write!(ffit, "{},", novfit[j]);
+        write!(ffit, "{},", novfit[j])?;
     }
-    write!(ffit, "\n");
+    writeln!(ffit)?;
     
     for i in 0..(evosteps / save_every) {
         // evole for some steps
         let fitness_history = mga.evolve(save_every as u32);
         
         // write "time" for the fitness file
-        write!(ffit, "{},", (i*save_every) as i32);
+        write!(ffit, "{},", (i*save_every) as i32)?;
         // network dir for this time step
         create_dir(format!("data/networks_nov{}/{}", iter_file, i*save_every))?;
         
         // lots to do in this loop over genomes
         for j in 0..gapop {
             // first just write the fitness value
-            write!(ffit, "{},", fitness_history[j]);
+            write!(ffit, "{},", fitness_history[j])?;
             // next make a file for the network structure and write to it
             let mut fnet = File::create(format!("data/networks_nov{}/{}/{}_adjmat_{}.csv", iter_file, i*save_every, j, species))?;
             let adjmat = GLV::vec_to_mat(&mga.genomes[j], species);
             for spi in 0..species {
                 for spj in 0..species {
-                    write!(fnet, "{},", adjmat[spi][spj]);
+                    write!(fnet, "{},", adjmat[spi][spj])?;
                 }
-                write!(fnet, "\n");
+                writeln!(fnet)?;
             }
 
         }
         // this adds the newline we need at the end of the fitness line
-        write!(ffit, "\n");
+        writeln!(ffit)?;
     }
     Ok(())
 }
-
-fn coexistence_search(genome: &Vec<u8>) -> f64 {
-    // GLV Constants
-    let species = 20;
-    let coeffs = 100;
-    let starts = 3;
-    let total = coeffs * starts;
-    let simtime = 30.0;
-    let simtimedt = 0.01;
-
-    // set everything up
-    let graph = GLV::vec_to_mat(genome, species);
-    let mut end_vec: Vec<Vec<f64>> = Vec::new();
-    
-    // iterate over sets of coefficients
-    for _c in 0..coeffs {
-        let mut glv_sim = GLV::new(species);
-        glv_sim.randomize_coeffs(&graph);
-
-        let comp_mat = glv_sim.a;
-
-        // for each set of coefficients we iterate over start conditions
-        for _s in 0..starts {
-            let mut glv_sim = GLV::new(species);
-            glv_sim.a = comp_mat.clone();
-            let mut init_state: Vec<f64> = vec![0.0; species];
-            for i in 0..species {
-                init_state[i] = rand::thread_rng().gen();
-            }
-            // run the simulation and save the final states
-            let sim_run = GLV::simulate(glv_sim, init_state, simtime, simtimedt);
-            let endstate = match sim_run.last().cloned() {
-                Some(last) => last,
-                _ => vec![0.0; species],
-            };
-            end_vec.push(endstate);
-        }
-    }
-     
-    // We will score each of these end states with species richness
-    let mut s_avg = 0.0;
-    for i in 0..end_vec.len() {
-        for j in 0..end_vec[0].len() {
-            if end_vec[i][j] > 0.01 {
-                s_avg += 1.0 / (total as f64);
-            }
-        }
-    }
-    let s_norm = s_avg / (species as f64);
-    return s_norm
-}
@@ -0,0 +1,169 @@
+use crate::glv::GLV;
+use crate::integrate::{self, Dynamics};
+use crate::trajectory::{Record, Trajectory};
+use crate::utils;
+
+// patches of GLV dynamics linked by dispersal. the state is patch major, ie
+// species i in patch p sits at p * n + i. species i leaves a patch at rate
+// dispersal[i] and the emigrants are split over its neighbours in
+// proportion to the link weights
+#[derive(Debug, Clone)]
+pub struct Metacommunity {
+    pub n: usize,  // Number of species
+    pub patches: Vec<GLV>,  // Local dynamics of every patch
+    pub links: Vec<Vec<f64>>,  // Dispersal weight from patch p to patch q
+    pub dispersal: Vec<f64>,  // Per capita emigration rate of each species
+}
+
+impl Metacommunity {
+    // the same local community copied into every patch
+    pub fn new(local: &GLV, links: Vec<Vec<f64>>, dispersal: Vec<f64>) -> Metacommunity {
+        assert_eq!(links.len(), links[0].len());
+        assert_eq!(dispersal.len(), local.n);
+        Metacommunity {
+            n: local.n,
+            patches: vec![local.clone(); links.len()],
+            links: links,
+            dispersal: dispersal,
+        }
+    }
+
+    // patches on a ring, each linked to its two neighbours
+    pub fn ring(local: &GLV, n_patches: usize, dispersal: Vec<f64>) -> Metacommunity {
+        let mut links = vec![vec![0.0; n_patches]; n_patches];
+        for p in 0..n_patches {
+            if n_patches > 1 {
+                links[p][(p + 1) % n_patches] = 1.0;
+                links[p][(p + n_patches - 1) % n_patches] = 1.0;
+            }
+        }
+        return Metacommunity::new(local, links, dispersal)
+    }
+
+    pub fn n_patches(&self) -> usize {
+        return self.patches.len()
+    }
+
+    pub fn patch<'a>(&self, state: &'a [f64], p: usize) -> &'a [f64] {
+        return &state[(p * self.n)..((p + 1) * self.n)]
+    }
+
+    // mean number of species above threshold in a patch
+    pub fn alpha_richness(&self, state: &[f64], threshold: f64) -> f64 {
+        let mut total = 0;
+        for p in 0..self.n_patches() {
+            total += utils::richness(self.patch(state, p), threshold);
+        }
+        return (total as f64) / (self.n_patches() as f64)
+    }
+
+    // number of species above threshold in at least one patch
+    pub fn gamma_richness(&self, state: &[f64], threshold: f64) -> usize {
+        let mut gamma = 0;
+        for i in 0..self.n {
+            if (0..self.n_patches()).any(|p| state[p * self.n + i] > threshold) {
+                gamma += 1;
+            }
+        }
+        return gamma
+    }
+
+    pub fn simulate(model: &Metacommunity, state: Vec<f64>, stop: f64, dt: f64,
+                    record: &Record) -> Trajectory {
+        return integrate::simulate(model, state, stop, dt, record)
+    }
+}
+
+impl Dynamics for Metacommunity {
+    fn dim(&self) -> usize {
+        return self.n * self.n_patches()
+    }
+
+    fn derivative(&self, t: f64, x: &Vec<f64>, out: &mut Vec<f64>) {
+        for p in 0..self.n_patches() {
+            let range = (p * self.n)..((p + 1) * self.n);
+            self.patches[p].rates(t, &x[range.clone()], &mut out[range]);
+        }
+
+        for p in 0..self.n_patches() {
+            let out_weight: f64 = self.links[p].iter().sum();
+            if out_weight == 0.0 {
+                continue;
+            }
+            for q in 0..self.n_patches() {
+                let share = self.links[p][q] / out_weight;
+                if share == 0.0 {
+                    continue;
+                }
+                for i in 0..self.n {
+                    let flow = self.dispersal[i] * share * x[p * self.n + i];
+                    out[p * self.n + i] -= flow;
+                    out[q * self.n + i] += flow;
+                }
+            }
+        }
+    }
+
+    // every patch gets its own arrivals
+    fn after_step(&self, x: &mut Vec<f64>, dt: f64) {
+        if !self.stochastic() {
+            return
+        }
+        let mut rng = rand::thread_rng();
+        for p in 0..self.n_patches() {
+            let range = (p * self.n)..((p + 1) * self.n);
+            self.patches[p].immigration.arrivals(&mut x[range], dt, &mut rng);
+        }
+    }
+
+    fn stochastic(&self) -> bool {
+        return self.patches.iter().any(|patch| patch.stochastic())
+    }
+}
+
+#[cfg(test)]
+mod test_metacommunity {
+    use super::*;
+    use crate::immigration::Immigration;
+
+    #[test]
+    fn test_dispersal_conserves_mass() {
+        // no local growth, so only dispersal moves things around
+        let mut local = GLV::new(2);
+        local.r = vec![0.0, 0.0];
+        let meta = Metacommunity::ring(&local, 3, vec![0.5, 0.1]);
+        let state = vec![1.0, 0.0, 0.0, 2.0, 0.0, 0.0];
+
+        let mut rate = vec![0.0; 6];
+        meta.derivative(0.0, &state, &mut rate);
+        assert_eq!(rate[0], -0.5);
+        assert_eq!(rate[2], 0.25);
+        assert!(rate.iter().sum::<f64>().abs() < 1e-12)
+    }
+
+    #[test]
+    fn test_richness() {
+        let local = GLV::new(2);
+        let meta = Metacommunity::ring(&local, 2, vec![0.0, 0.0]);
+        let state = vec![0.5, 0.0, 0.0, 0.0];
+
+        assert_eq!(meta.alpha_richness(&state, 0.01), 0.5);
+        assert_eq!(meta.gamma_richness(&state, 0.01), 1);
+
+        let out = Metacommunity::simulate(&meta, state, 5.0, 0.01, &Record::Final);
+        assert_eq!(meta.gamma_richness(out.last().unwrap(), 0.01), 1)
+    }
+
+    #[test]
+    fn test_stochastic_patch() {
+        // only the second patch gets arrivals, and certainly does
+        let local = GLV::new(2);
+        let mut meta = Metacommunity::ring(&local, 2, vec![0.0, 0.0]);
+        meta.patches[1].immigration = Immigration::Stochastic { rates: vec![1e9, 0.0], propagule: 0.01 };
+        assert!(meta.stochastic());
+
+        let mut state = vec![0.0; 4];
+        meta.after_step(&mut state, 0.01);
+        assert_eq!(state, vec![0.0, 0.0, 0.01, 0.0])
+    }
+}
@@ -0,0 +1,214 @@
+use crate::glv::{GLV, Kernel};
+use crate::vmath;
+
+// what makes an equilibrium a good one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Objective {
+    MinAbundance,  // smallest x*_k
+    FeasibilityMargin,  // smallest x*_k relative to the mean, ignores overall scale
+}
+
+// knobs for optimize
+#[derive(Debug, Clone)]
+pub struct OptimizeParams {
+    pub objective: Objective,
+    pub softness: f64,  // temperature of the softmin standing in for the min
+    pub lower: f64,  // bounds on every tuned a_ij, competition only by default
+    pub upper: f64,
+    pub step: f64,  // first step size of the line search
+    pub iters: usize,
+    pub tol: f64,  // stop once an iteration gains less than this
+}
+
+impl Default for OptimizeParams {
+    fn default() -> OptimizeParams {
+        OptimizeParams {
+            objective: Objective::MinAbundance,
+            softness: 0.01,
+            lower: 0.0,
+            upper: 2.0,
+            step: 0.1,
+            iters: 200,
+            tol: 1e-9,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Optimized {
+    pub glv: GLV,  // a copy of the start with the tuned a
+    pub equilibrium: Vec<f64>,
+    pub objective: f64,
+    pub history: Vec<f64>,  // objective after each accepted step, starting point first
+}
+
+// -t ln sum_k exp(-y_k / t) and its weights d/dy_k, shifted by the min so
+// the exponentials stay finite
+fn softmin(y: &Vec<f64>, t: f64) -> (f64, Vec<f64>) {
+    let lo = y.iter().cloned().fold(f64::INFINITY, f64::min);
+    let e: Vec<f64> = y.iter().map(|yk| (-(yk - lo) / t).exp()).collect();
+    let total: f64 = e.iter().sum();
+    let value = lo - t * total.ln();
+    return (value, e.iter().map(|ek| ek / total).collect())
+}
+
+// objective at the interior equilibrium a x* = 1 and its gradient in a.
+// with g = d objective / d x* the adjoint a^T l = g gives
+// d objective / d a_ij = -l_i x*_j, one extra solve for the whole gradient
+fn evaluate(a: &Vec<Vec<f64>>, params: &OptimizeParams) -> Option<(f64, Vec<f64>, Vec<Vec<f64>>)> {
+    let n = a.len();
+    let x = vmath::solve(a, &vec![1.0; n])?;
+
+    let (value, g) = match params.objective {
+        Objective::MinAbundance => softmin(&x, params.softness),
+        Objective::FeasibilityMargin => {
+            let m = x.iter().sum::<f64>() / (n as f64);
+            let y: Vec<f64> = x.iter().map(|xk| xk / m).collect();
+            let (value, w) = softmin(&y, params.softness);
+            let wx: f64 = (0..n).map(|k| w[k] * x[k]).sum();
+            (value, (0..n).map(|l| w[l] / m - wx / (m * m * (n as f64))).collect())
+        },
+    };
+
+    let mut at = vec![vec![0.0; n]; n];
+    for i in 0..n {
+        for j in 0..n {
+            at[i][j] = a[j][i];
+        }
+    }
+    let l = vmath::solve(&at, &g)?;
+    let mut grad = vec![vec![0.0; n]; n];
+    for i in 0..n {
+        for j in 0..n {
+            grad[i][j] = -l[i] * x[j];
+        }
+    }
+    return Some((value, x, grad))
+}
+
+// projected gradient ascent on the off diagonal a_ij the template allows,
+// starting from the current a (eg after randomize_coeffs) and staying inside
+// [lower, upper]. the diagonal and the links the template leaves out are not
+// touched. None when the start has no equilibrium to speak of, which like
+// GLV::equilibrium includes every nonlinear kernel
+pub fn optimize(glv: &GLV, template: &Vec<Vec<u8>>, params: &OptimizeParams) -> Option<Optimized> {
+    let n = glv.n;
+    assert!((template.len() == n) && template.iter().all(|row| row.len() == n), "template has to be n x n");
+    if glv.kernel != Kernel::Linear {
+        return None
+    }
+    let edges: Vec<(usize, usize)> = (0..n)
+        .flat_map(|i| (0..n).map(move |j| (i, j)))
+        .filter(|(i, j)| (i != j) && (template[*i][*j] == 1))
+        .collect();
+
+    let mut a = glv.a.clone();
+    for (i, j) in edges.iter() {
+        a[*i][*j] = a[*i][*j].max(params.lower).min(params.upper);
+    }
+    let (mut value, mut x, mut grad) = evaluate(&a, params)?;
+    let mut history = vec![value];
+    let mut step = params.step;
+
+    for _ in 0..params.iters {
+        // backtrack until the projected step gains enough (armijo)
+        let mut accepted = false;
+        for _ in 0..30 {
+            let mut trial = a.clone();
+            let mut ascent = 0.0;
+            for (i, j) in edges.iter() {
+                let moved = (a[*i][*j] + step * grad[*i][*j]).max(params.lower).min(params.upper);
+                ascent += grad[*i][*j] * (moved - a[*i][*j]);
+                trial[*i][*j] = moved;
+            }
+            if ascent <= 0.0 {
+                break;
+            }
+            if let Some((v, tx, tg)) = evaluate(&trial, params) {
+                if v >= value + 1e-4 * ascent {
+                    let gain = v - value;
+                    a = trial;
+                    value = v;
+                    x = tx;
+                    grad = tg;
+                    history.push(value);
+                    step *= 1.5;
+                    accepted = gain >= params.tol;
+                    break;
+                }
+            }
+            step *= 0.5;
+        }
+        if !accepted {
+            break;
+        }
+    }
+
+    let mut out = glv.clone();
+    out.a = a;
+    Some(Optimized {
+        glv: out,
+        equilibrium: x,
+        objective: value,
+        history: history,
+    })
+}
+
+#[cfg(test)]
+mod test_optimize {
+    use super::*;
+
+    fn start() -> GLV {
+        let mut glv = GLV::new(3);
+        glv.a = vec![vec![1.0, 0.9, 0.2],
+                     vec![0.6, 1.0, 1.5],
+                     vec![0.3, 0.4, 1.0]];
+        glv
+    }
+
+    #[test]
+    fn test_gradient() {
+        let glv = start();
+        for objective in [Objective::MinAbundance, Objective::FeasibilityMargin].iter() {
+            let params = OptimizeParams { objective: *objective, softness: 0.1, ..OptimizeParams::default() };
+            let (_, _, grad) = evaluate(&glv.a, &params).unwrap();
+            let h = 1e-6;
+            for i in 0..3 {
+                for j in 0..3 {
+                    let mut up = glv.a.clone();
+                    let mut down = glv.a.clone();
+                    up[i][j] += h;
+                    down[i][j] -= h;
+                    let fd = (evaluate(&up, &params).unwrap().0 - evaluate(&down, &params).unwrap().0) / (2.0 * h);
+                    assert!((grad[i][j] - fd).abs() < 1e-6);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_optimize_min_abundance() {
+        // species 1 is excluded at the start
+        let glv = start();
+        assert!(!GLV::is_feasible(&glv.equilibrium().unwrap()));
+        let mut template = vec![vec![1; 3]; 3];
+        template[2][0] = 0;
+        let params = OptimizeParams { upper: 1.0, ..OptimizeParams::default() };
+        let out = optimize(&glv, &template, &params).unwrap();
+
+        assert!(GLV::is_feasible(&out.equilibrium));
+        assert!(out.objective > out.history[0]);
+        assert!(out.history.windows(2).all(|w| w[1] >= w[0]));
+        // off template links are left alone and the bounds hold
+        assert_eq!(out.glv.a[2][0], 0.3);
+        assert_eq!(out.glv.a[1][1], 1.0);
+        assert!(out.glv.a.iter().flatten().all(|v| (*v >= 0.0) && (*v <= 1.0)))
+    }
+
+    #[test]
+    fn test_nonlinear_kernel() {
+        let mut glv = start();
+        glv.kernel = Kernel::uniform_holling_ii(3, 0.5);
+        assert!(optimize(&glv, &vec![vec![1; 3]; 3], &OptimizeParams::default()).is_none())
+    }
+}
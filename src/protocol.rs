@@ -0,0 +1,99 @@
+use crate::glv::GLV;
+use crate::integrate;
+use crate::trajectory::{Event, Record, Recorder, Trajectory};
+
+// things we can do to a community in the middle of a run
+#[derive(Debug, Clone, PartialEq)]
+pub enum Intervention {
+    Pulse { species: usize, fraction: f64 },  // remove a fraction of the density
+    Harvest { species: usize, effort: f64 },  // per capita effort from now on, 0 stops it
+    Introduce { species: usize, density: f64 },  // add individuals
+    SetGrowth { species: usize, value: f64 },
+    SetInteraction { i: usize, j: usize, value: f64 },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scheduled {
+    pub time: f64,
+    pub action: Intervention,
+}
+
+impl Intervention {
+    pub fn apply(&self, model: &mut GLV, x: &mut Vec<f64>) {
+        match *self {
+            Intervention::Pulse { species, fraction } => x[species] *= 1.0 - fraction,
+            Intervention::Harvest { species, effort } => model.harvest[species] = effort,
+            Intervention::Introduce { species, density } => x[species] += density,
+            Intervention::SetGrowth { species, value } => model.r[species] = value,
+            Intervention::SetInteraction { i, j, value } => model.a[i][j] = value,
+        }
+    }
+}
+
+// GLV::simulate_with, but interventions are applied at the first step whose
+// time reaches their scheduled time. the recorded state at that time is the
+// one before the intervention and the trajectory's events say when each one
+// went in
+pub fn simulate(mut model: GLV, state: Vec<f64>, stop: f64, dt: f64, record: &Record,
+                schedule: &Vec<Scheduled>) -> Trajectory {
+    assert_eq!(state.len(), model.n);
+    let mut order: Vec<usize> = (0..schedule.len()).collect();
+    order.sort_by(|p, q| schedule[*p].time.total_cmp(&schedule[*q].time));
+    let mut next_event = 0;
+
+    let steps = integrate::steps(stop, dt);
+    let mut rec = Recorder::new(record, model.n, steps);
+    let mut x = state;
+    let mut next = vec![0.0; model.n];
+    rec.observe(0, 0.0, &x);
+
+    for step in 1..steps {
+        let t = ((step - 1) as f64) * dt;
+        while (next_event < order.len()) && (schedule[order[next_event]].time <= t) {
+            let k = order[next_event];
+            schedule[k].action.apply(&mut model, &mut x);
+            rec.event(Event { time: t, step: step, index: k });
+            next_event += 1;
+        }
+        integrate::euler_step(&model, t, dt, &x, &mut next);
+        rec.observe(step, (step as f64) * dt, &next);
+        std::mem::swap(&mut x, &mut next);
+    }
+
+    let last = steps.max(1) - 1;
+    return rec.finish(last, (last as f64) * dt, &x)
+}
+
+#[cfg(test)]
+mod test_protocol {
+    use super::*;
+
+    #[test]
+    fn test_pulse_and_recovery() {
+        let glv = GLV::new(1);
+        let schedule = vec![Scheduled { time: 20.0, action: Intervention::Pulse { species: 0, fraction: 0.5 } }];
+        let out = simulate(glv, vec![1.0], 40.0, 0.01, &Record::Full, &schedule);
+
+        assert_eq!(out.events.len(), 1);
+        assert_eq!(out.events[0].step, 2001);
+        // halved right after the pulse and back at carrying capacity by the end
+        assert!((out[2001][0] - 0.5).abs() < 0.01);
+        assert!((out.last().unwrap()[0] - 1.0).abs() < 1e-3)
+    }
+
+    #[test]
+    fn test_harvest_and_schedule_order() {
+        let glv = GLV::new(1);
+        // given out of order on purpose
+        let schedule = vec![
+            Scheduled { time: 5.0, action: Intervention::Harvest { species: 0, effort: 0.0 } },
+            Scheduled { time: 1.0, action: Intervention::Harvest { species: 0, effort: 0.5 } },
+        ];
+        let out = simulate(glv, vec![1.0], 3.0, 0.01, &Record::Final, &schedule);
+
+        // harvesting lowers the equilibrium to 1 - effort / r
+        assert_eq!(out.events.len(), 1);
+        assert_eq!(out.events[0].index, 1);
+        assert!(out[0][0] < 0.7)
+    }
+}
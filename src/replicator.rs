@@ -0,0 +1,171 @@
+use crate::glv::{GLV, Kernel};
+use crate::integrate::{self, Dynamics};
+use crate::trajectory::{Record, Trajectory};
+
+// replicator equation y_i' = y_i ((A y)_i - y.A y) on the simplex
+#[derive(Debug, Clone)]
+pub struct Replicator {
+    pub n: usize,  // Number of strategies
+    pub payoff: Vec<Vec<f64>>,  // Payoff to i when playing against j
+}
+
+impl Replicator {
+    pub fn new(n_strategies: usize) -> Replicator {
+        Replicator {
+            n: n_strategies,
+            payoff: vec![vec![0.0; n_strategies]; n_strategies],
+        }
+    }
+
+    // a tournament from a 0/1 graph, template[i][j] == 1 means i beats j.
+    // a directed 3 cycle gives rock paper scissors
+    pub fn tournament(template: &Vec<Vec<u8>>) -> Replicator {
+        let mut rep = Replicator::new(template.len());
+        for i in 0..rep.n {
+            for j in 0..rep.n {
+                if (i != j) && (template[i][j] == 1) {
+                    rep.payoff[i][j] += 1.0;
+                    rep.payoff[j][i] -= 1.0;
+                }
+            }
+        }
+        return rep
+    }
+
+    // Hofbauer's map from GLV on n species to replicator on n + 1
+    // strategies. with x_i' = x_i (r_i + sum_j B_ij x_j) and B_ij = -r_i a_ij
+    // the payoff is [[B, r], [0, 0]]
+    pub fn from_glv(glv: &GLV) -> Replicator {
+        assert!(glv.kernel == Kernel::Linear, "only linear GLV maps onto replicator dynamics");
+        let mut rep = Replicator::new(glv.n + 1);
+        for i in 0..glv.n {
+            for j in 0..glv.n {
+                rep.payoff[i][j] = -glv.r[i] * glv.a[i][j];
+            }
+            rep.payoff[i][glv.n] = glv.r[i];
+        }
+        return rep
+    }
+
+    // the way back. adding a constant to a column of the payoff does not
+    // change the dynamics, so first zero the last row. strategies with no
+    // payoff against the last one (r_i = 0) get an empty row of a
+    pub fn to_glv(&self) -> GLV {
+        assert!(self.n > 1);
+        let species = self.n - 1;
+        let mut glv = GLV::new(species);
+        for i in 0..species {
+            let r_i = self.payoff[i][species] - self.payoff[species][species];
+            glv.r[i] = r_i;
+            for j in 0..species {
+                let b_ij = self.payoff[i][j] - self.payoff[species][j];
+                glv.a[i][j] = if r_i != 0.0 { -b_ij / r_i } else { 0.0 };
+            }
+        }
+        return glv
+    }
+
+    // y_i = x_i / (1 + sum x), with the extra strategy taking 1 / (1 + sum x)
+    pub fn glv_to_simplex(x: &Vec<f64>) -> Vec<f64> {
+        let total: f64 = 1.0 + x.iter().sum::<f64>();
+        let mut y: Vec<f64> = x.iter().map(|xi| xi / total).collect();
+        y.push(1.0 / total);
+        return y
+    }
+
+    pub fn simplex_to_glv(y: &Vec<f64>) -> Vec<f64> {
+        let last = y[y.len() - 1];
+        return y[..(y.len() - 1)].iter().map(|yi| yi / last).collect()
+    }
+
+    pub fn simulate(model: &Replicator, state: Vec<f64>, stop: f64, dt: f64, record: &Record) -> Trajectory {
+        return integrate::simulate(model, state, stop, dt, record)
+    }
+}
+
+impl Dynamics for Replicator {
+    fn dim(&self) -> usize {
+        return self.n
+    }
+
+    fn derivative(&self, _t: f64, y: &Vec<f64>, out: &mut Vec<f64>) {
+        let mut mean_payoff = 0.0;
+        for i in 0..self.n {
+            let mut fit = 0.0;
+            for j in 0..self.n {
+                fit += self.payoff[i][j] * y[j];
+            }
+            out[i] = fit;
+            mean_payoff += y[i] * fit;
+        }
+        for i in 0..self.n {
+            out[i] = y[i] * (out[i] - mean_payoff);
+        }
+    }
+
+    // euler drifts off the simplex, so pull it back every step
+    fn after_step(&self, y: &mut Vec<f64>, _dt: f64) {
+        let mut total = 0.0;
+        for i in 0..self.n {
+            y[i] = y[i].max(0.0);
+            total += y[i];
+        }
+        if total > 0.0 {
+            for i in 0..self.n {
+                y[i] /= total;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_replicator {
+    use super::*;
+
+    #[test]
+    fn test_glv_round_trip() {
+        let mut glv = GLV::new(2);
+        glv.r = vec![1.0, 2.0];
+        glv.a[0][1] = 0.5;
+        glv.a[1][0] = 0.25;
+
+        let back = Replicator::from_glv(&glv).to_glv();
+        assert_eq!(back.r, glv.r);
+        assert_eq!(back.a, glv.a);
+
+        let x = vec![0.5, 3.0];
+        let round = Replicator::simplex_to_glv(&Replicator::glv_to_simplex(&x));
+        assert!((round[0] - 0.5).abs() < 1e-12);
+        assert!((round[1] - 3.0).abs() < 1e-12)
+    }
+
+    #[test]
+    fn test_equilibrium_maps_to_rest_point() {
+        let mut glv = GLV::new(2);
+        glv.a[0][1] = 0.5;
+        glv.a[1][0] = 0.5;
+        // interior equilibrium at x = 2/3 for both
+        let y = Replicator::glv_to_simplex(&vec![2.0 / 3.0, 2.0 / 3.0]);
+
+        let rep = Replicator::from_glv(&glv);
+        let mut rate = vec![0.0; 3];
+        rep.derivative(0.0, &y, &mut rate);
+        for i in 0..3 {
+            assert!(rate[i].abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_rock_paper_scissors() {
+        let rps = Replicator::tournament(&vec![vec![0, 1, 0],
+                                               vec![0, 0, 1],
+                                               vec![1, 0, 0]]);
+        assert_eq!(rps.payoff[0][1], 1.0);
+        assert_eq!(rps.payoff[1][0], -1.0);
+
+        let out = Replicator::simulate(&rps, vec![0.5, 0.3, 0.2], 10.0, 0.01, &Record::Final);
+        let end = out.last().unwrap();
+        assert!((end.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+        assert!(end.iter().all(|y| *y > 0.0))
+    }
+}
@@ -0,0 +1,146 @@
+use crate::glv::GLV;
+use crate::integrate::{self, Dynamics};
+use crate::vmath;
+use rand::prelude::*;
+
+// local stability of an equilibrium
+#[derive(Debug, Clone)]
+pub struct Resilience {
+    pub equilibrium: Vec<f64>,
+    pub resilience: f64,  // -max Re(lambda) of the jacobian, > 0 when stable
+    pub reactivity: f64,  // largest eigenvalue of (J + J^T) / 2
+    pub stable: bool,
+}
+
+// what happened to a batch of random kicks away from the equilibrium
+#[derive(Debug, Clone)]
+pub struct ReturnTime {
+    pub mean: f64,  // over the runs that came back
+    pub max: f64,
+    pub returned: usize,  // runs that got back within max_time
+    pub trials: usize,
+}
+
+// knobs for return_time
+#[derive(Debug, Clone)]
+pub struct Perturbation {
+    pub size: f64,  // each x*_i is scaled by 1 + size * U(-1, 1)
+    pub trials: usize,
+    pub tol: f64,  // back when max_i |x_i - x*_i| is below this
+    pub dt: f64,
+    pub max_time: f64,
+}
+
+impl Default for Perturbation {
+    fn default() -> Perturbation {
+        Perturbation {
+            size: 0.1,
+            trials: 10,
+            tol: 1e-3,
+            dt: 0.01,
+            max_time: 100.0,
+        }
+    }
+}
+
+pub fn asymptotic_resilience(jac: &Vec<Vec<f64>>) -> Option<f64> {
+    let ev = vmath::eigenvalues(jac)?;
+    let max_re = ev.iter().map(|e| e.0).fold(f64::NEG_INFINITY, f64::max);
+    return Some(-max_re)
+}
+
+pub fn reactivity(jac: &Vec<Vec<f64>>) -> Option<f64> {
+    let n = jac.len();
+    let mut sym = vec![vec![0.0; n]; n];
+    for i in 0..n {
+        for j in 0..n {
+            sym[i][j] = 0.5 * (jac[i][j] + jac[j][i]);
+        }
+    }
+    let ev = vmath::eigenvalues(&sym)?;
+    return Some(ev.iter().map(|e| e.0).fold(f64::NEG_INFINITY, f64::max))
+}
+
+// stability of a given equilibrium of any model we can integrate
+pub fn analyze_at<D: Dynamics>(model: &D, equilibrium: &Vec<f64>) -> Option<Resilience> {
+    let jac = integrate::jacobian(model, 0.0, equilibrium);
+    let resilience = asymptotic_resilience(&jac)?;
+    Some(Resilience {
+        equilibrium: equilibrium.clone(),
+        resilience: resilience,
+        reactivity: reactivity(&jac)?,
+        stable: resilience > 0.0,
+    })
+}
+
+// the interior equilibrium of a GLV, None when it is missing or infeasible
+pub fn analyze(glv: &GLV) -> Option<Resilience> {
+    let x = glv.equilibrium()?;
+    if !GLV::is_feasible(&x) {
+        return None
+    }
+    return analyze_at(glv, &x)
+}
+
+pub fn return_time<D: Dynamics>(model: &D, equilibrium: &Vec<f64>, kick: &Perturbation) -> ReturnTime {
+    let mut rng = thread_rng();
+    let n = model.dim();
+    let mut times: Vec<f64> = Vec::with_capacity(kick.trials);
+    let mut x = vec![0.0; n];
+    let mut next = vec![0.0; n];
+
+    for _ in 0..kick.trials {
+        for i in 0..n {
+            x[i] = equilibrium[i] * (1.0 + kick.size * rng.gen_range(-1.0..1.0));
+        }
+        let mut t = 0.0;
+        while t < kick.max_time {
+            let dist = (0..n).map(|i| (x[i] - equilibrium[i]).abs()).fold(0.0, f64::max);
+            if dist < kick.tol {
+                times.push(t);
+                break;
+            }
+            integrate::euler_step(model, t, kick.dt, &x, &mut next);
+            std::mem::swap(&mut x, &mut next);
+            t += kick.dt;
+        }
+    }
+
+    let returned = times.len();
+    let mean = if returned > 0 { times.iter().sum::<f64>() / (returned as f64) } else { f64::INFINITY };
+    ReturnTime {
+        mean: mean,
+        max: times.iter().cloned().fold(0.0, f64::max),
+        returned: returned,
+        trials: kick.trials,
+    }
+}
+
+#[cfg(test)]
+mod test_resilience {
+    use super::*;
+
+    #[test]
+    fn test_analyze() {
+        let mut glv = GLV::new(2);
+        glv.a[0][1] = 0.5;
+        glv.a[1][0] = 0.5;
+        let res = analyze(&glv).unwrap();
+
+        // J = -diag(x*) a with x* = 2/3, eigenvalues -2/3 (1 +- 0.5)
+        assert!(res.stable);
+        assert!((res.resilience - 1.0 / 3.0).abs() < 1e-6);
+        assert!((res.reactivity + 1.0 / 3.0).abs() < 1e-6)
+    }
+
+    #[test]
+    fn test_return_time() {
+        let glv = GLV::new(1);
+        let kick = Perturbation { size: 0.5, trials: 5, ..Perturbation::default() };
+        let back = return_time(&glv, &vec![1.0], &kick);
+
+        // logistic return from at most half off takes about ln(0.5 / tol)
+        assert_eq!(back.returned, 5);
+        assert!(back.max < 7.0)
+    }
+}
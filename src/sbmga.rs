@@ -1,37 +1,57 @@
 use rand::Rng;
-use std::thread;
-use std::sync::mpsc;
-use crate::utils::hamming;
+use crate::genome::Genome;
+
+// how a step picks who reproduces and who gets replaced
+#[derive(Debug, Clone, PartialEq)]
+pub enum Selection {
+    Microbial,  // two players in a ring deme, the loser is infected by the winner
+    Tournament(usize),  // k random players, the worst is infected by the best
+    Proportional,  // a new generation from parents drawn by fitness
+    Rank,  // a new generation from parents drawn by fitness rank
+    MuPlusLambda(usize),  // lambda offspring, the best pop_size of parents and offspring survive
+    MuCommaLambda(usize),  // lambda >= pop_size offspring, the best pop_size of them survive
+    SteadyState { elites: usize },  // one child per step, it never replaces the top elites
+}
+
+// gets the step number, the population and the current fitness of each
+// genome. a closure, so it can keep whatever it logs
+pub type Observer<G> = Box<dyn FnMut(u32, &[G], &[f64])>;
 
-pub struct MGA {
-    pub fitness: fn(&Vec<u8>) -> f64,
-    pub fitness_values: Vec<f64>,
+pub struct MGA<G: Genome> {
+    pub fitness: fn(&G) -> f64,
+    pub fitness_values: Vec<f64>,  // last evaluated fitness of each genome
     pub pop_size: usize,
     pub gene_size: usize,
     deme_size: usize,
-    pub genomes: Vec<Vec<u8>>,
+    pub genomes: Vec<G>,
+    pub genome_params: G::Params,  // mutation and crossover settings of the genome type
+    pub selection: Selection,
+    pub observer: Option<Observer<G>>,  // called after every step of evolve
+    pub quiet: bool,  // no per step printing
+    stale: Vec<bool>,  // genome changed since its fitness_values entry
     mut_prob: f64,
     inf_prob: f64,
 }
 
-impl MGA {
-    pub fn new(ffunc: fn(&Vec<u8>) -> f64, population: usize, genes: usize, 
-           deme: usize, mp: f64, ip: f64) -> MGA {
+impl<G: Genome> MGA<G> {
+    pub fn new(ffunc: fn(&G) -> f64, population: usize, genes: usize,
+           deme: usize, mp: f64, ip: f64) -> MGA<G> {
+        return MGA::with_params(ffunc, population, genes, deme, mp, ip, G::Params::default())
+    }
+
+    pub fn with_params(ffunc: fn(&G) -> f64, population: usize, genes: usize,
+                       deme: usize, mp: f64, ip: f64, params: G::Params) -> MGA<G> {
         // this can break shit
-        assert!(population - 1 >= deme);
+        assert!(population > deme);
 
         // rng for initialization
         let mut rng = rand::thread_rng();
         let mut fvec: Vec<f64> = Vec::new();
-        let mut gvec: Vec<Vec<u8>> = Vec::new();
+        let mut gvec: Vec<G> = Vec::new();
 
-        // make a random vector of zeros and ones
+        // random genomes to start from
         for _i in 0..population {
-            let mut new_genome: Vec<u8> = Vec::new();
-            for _j in 0..genes {
-                new_genome.push(rng.gen_range(0..2));
-            }
-            gvec.push(new_genome);
+            gvec.push(G::random(genes, &params, &mut rng));
             fvec.push(0.0);
         }
 
@@ -42,6 +62,11 @@ impl MGA {
             gene_size: genes,
             deme_size: deme,
             genomes: gvec,
+            genome_params: params,
+            selection: Selection::Microbial,
+            observer: None,
+            quiet: false,
+            stale: vec![true; population],
             mut_prob: mp,
             inf_prob: ip,
         };
@@ -80,38 +105,199 @@ impl MGA {
         return [win, lose]
     }
 
+    fn log(&self, line: String) {
+        if !self.quiet {
+            println!("{}", line);
+        }
+    }
+
     fn step(&mut self) {
+        match self.selection.clone() {
+            Selection::Microbial => self.step_microbial(),
+            Selection::Tournament(k) => self.step_tournament(k),
+            Selection::Proportional => self.step_generational(false),
+            Selection::Rank => self.step_generational(true),
+            Selection::MuPlusLambda(lambda) => self.step_strategy(lambda, true),
+            Selection::MuCommaLambda(lambda) => self.step_strategy(lambda, false),
+            Selection::SteadyState { elites } => self.step_steady_state(elites),
+        }
+    }
+
+    // scores both players afresh every time, so a lucky draw of a noisy
+    // fitness does not stick to a genome
+    fn step_microbial(&mut self) {
         // pick competitors and get the winners
         let comps = self.pick_competitors(0, false);
         let outs = self.compete(comps[0], comps[1]);
-        println!("{} beats {}", outs[0], outs[1]);
+        self.log(format!("{} beats {}", outs[0], outs[1]));
+        self.reproduce(outs[0], outs[1]);
+        self.stale[outs[1]] = true;
+    }
+
+    fn step_tournament(&mut self, k: usize) {
+        let mut rng = rand::thread_rng();
+        self.refresh();
+        let players = rand::seq::index::sample(&mut rng, self.pop_size, k.max(2).min(self.pop_size)).into_vec();
+        let by_fitness = |a: &&usize, b: &&usize| self.fitness_values[**a].total_cmp(&self.fitness_values[**b]);
+        let win = *players.iter().max_by(by_fitness).unwrap();
+        let lose = *players.iter().min_by(by_fitness).unwrap();
+        self.log(format!("{} beats {}", win, lose));
+        self.reproduce(win, lose);
+        self.rescore(lose);
+    }
 
-        for gene_i in 0..self.gene_size {
-            // infect
-            let inf_roll: f64 = rand::thread_rng().gen();
-            if inf_roll < self.inf_prob {
-                self.genomes[outs[1]][gene_i] = self.genomes[outs[0]][gene_i];
+    // a whole new population, each child a copy of one parent infected by
+    // another and mutated. parents are drawn by fitness or by rank
+    fn step_generational(&mut self, rank: bool) {
+        let mut rng = rand::thread_rng();
+        self.refresh();
+        let fits = self.fitness_values.clone();
+        let weights: Vec<f64> = if rank {
+            let order = ranking(&fits);
+            let mut w = vec![0.0; self.pop_size];
+            for (place, i) in order.iter().enumerate() {
+                w[*i] = (self.pop_size - place) as f64;
             }
+            w
+        } else {
+            // shift so the worst still has a small chance
+            let lo = fits.iter().cloned().fold(f64::INFINITY, f64::min);
+            let hi = fits.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+            let floor = 1e-3 * (hi - lo).max(1e-12);
+            fits.iter().map(|f| f - lo + floor).collect()
+        };
+        let mut next: Vec<G> = Vec::with_capacity(self.pop_size);
+        for _ in 0..self.pop_size {
+            let a = pick_weighted(&weights, &mut rng);
+            let b = pick_weighted(&weights, &mut rng);
+            next.push(self.offspring(a, b));
+        }
+        self.genomes = next;
+        self.stale = vec![true; self.pop_size];
+        self.log_generation(&fits);
+    }
 
-            // mutate
-            let mut_roll: f64 = rand::thread_rng().gen();
-            if mut_roll < self.mut_prob {
-                self.genomes[outs[1]][gene_i] = (self.genomes[outs[1]][gene_i] + 1) % 2;
+    // evolution strategies, lambda offspring from uniformly drawn parents and
+    // truncation to the best pop_size, with or without the parents
+    fn step_strategy(&mut self, lambda: usize, plus: bool) {
+        let mut rng = rand::thread_rng();
+        self.refresh();
+        let fits = self.fitness_values.clone();
+        let mut pool: Vec<(G, f64)> = Vec::with_capacity(self.pop_size + lambda);
+        if plus {
+            for i in 0..self.pop_size {
+                pool.push((self.genomes[i].clone(), fits[i]));
             }
+        } else {
+            assert!(lambda >= self.pop_size);
+        }
+        for _ in 0..lambda {
+            let a = rng.gen_range(0..self.pop_size);
+            let b = rng.gen_range(0..self.pop_size);
+            let child = self.offspring(a, b);
+            let f = (self.fitness)(&child);
+            pool.push((child, f));
         }
+        pool.sort_by(|x, y| y.1.total_cmp(&x.1));
+        pool.truncate(self.pop_size);
+        self.fitness_values = pool.iter().map(|p| p.1).collect();
+        self.genomes = pool.into_iter().map(|p| p.0).collect();
+        self.log_generation(&fits);
+    }
+
+    // one child from two binary tournament winners replaces a random member
+    // of the population outside the elites
+    fn step_steady_state(&mut self, elites: usize) {
+        let mut rng = rand::thread_rng();
+        self.refresh();
+        let elites = elites.min(self.pop_size - 1);
+        let order = ranking(&self.fitness_values);
+        let comps = self.pick_competitors(0, false);
+        let a = self.compete(comps[0], comps[1])[0];
+        let comps = self.pick_competitors(0, false);
+        let b = self.compete(comps[0], comps[1])[0];
+        let child = self.offspring(a, b);
+        let lose = order[rng.gen_range(elites..self.pop_size)];
+        self.fitness_values[lose] = (self.fitness)(&child);
+        self.genomes[lose] = child;
+        self.log(format!("{} and {} replace {}", a, b, lose));
+    }
+
+    fn offspring(&self, a: usize, b: usize) -> G {
+        let mut rng = rand::thread_rng();
+        let mut child = self.genomes[a].clone();
+        child.infect(&self.genomes[b], self.inf_prob, &self.genome_params, &mut rng);
+        child.mutate(self.mut_prob, &self.genome_params, &mut rng);
+        return child
+    }
+
+    // after genome i changed in place
+    fn rescore(&mut self, i: usize) {
+        self.fitness_values[i] = (self.fitness)(&self.genomes[i]);
+        self.stale[i] = false;
+    }
+
+    // evaluate whoever changed since their last evaluation
+    fn refresh(&mut self) {
+        for i in 0..self.pop_size {
+            if self.stale[i] {
+                self.rescore(i);
+            }
+        }
+    }
+
+    fn log_generation(&self, fits: &Vec<f64>) {
+        let best = fits.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let mean = fits.iter().sum::<f64>() / (fits.len() as f64);
+        self.log(format!("best {} mean {}", best, mean));
+    }
+
+    // the loser picks up some of the winner's genes and then mutates
+    fn reproduce(&mut self, win: usize, lose: usize) {
+        let mut rng = rand::thread_rng();
+        let winner = self.genomes[win].clone();
+        self.genomes[lose].infect(&winner, self.inf_prob, &self.genome_params, &mut rng);
+        self.genomes[lose].mutate(self.mut_prob, &self.genome_params, &mut rng);
+    }
+
+    fn get_fitness(&self) -> Vec<f64> {
+        let mut fit_vec: Vec<f64> = Vec::new();
+        for i in 0..self.pop_size {
+            fit_vec.push((self.fitness)(&self.genomes[i]));
+        }
+        return fit_vec
+    }
+
+    pub fn evolve(&mut self, n_steps: u32) -> Vec<f64> {
+        // the genomes may have been swapped out since the last call
+        self.stale = vec![true; self.pop_size];
+        for t in 0..n_steps {
+            self.step();
+            self.log("Step".to_string());
+            if self.observer.is_some() {
+                self.refresh();
+            }
+            if let Some(observe) = self.observer.as_mut() {
+                observe(t, &self.genomes, &self.fitness_values);
+            }
+        }
+
+        // the values the observer saw last
+        self.refresh();
+        return self.fitness_values.clone();
     }
 
     fn compete_novelty(&mut self, i: usize, j: usize) -> [usize; 2] {
-        let mut fit_i: usize = 0;
-        let mut fit_j: usize = 0;
-        let mut dist: usize;
-        // maximize hamming distance for fitness
+        let mut fit_i: f64 = 0.0;
+        let mut fit_j: f64 = 0.0;
+        let mut dist: f64;
+        // maximize distance to the rest of the population for fitness
         for k in 0..self.pop_size {
-            dist = hamming(&self.genomes[i], &self.genomes[k]);
+            dist = self.genomes[i].distance(&self.genomes[k]);
             if dist > fit_i {
                 fit_i = dist;
             }
-            dist = hamming(&self.genomes[j], &self.genomes[k]);
+            dist = self.genomes[j].distance(&self.genomes[k]);
             if dist > fit_j {
                 fit_j = dist;
             }
@@ -134,53 +320,45 @@ impl MGA {
     fn step_novelty(&mut self) {
         let comps = self.pick_competitors(0, false);
         let outs = self.compete_novelty(comps[0], comps[1]);
-        println!("{} beats {}", outs[0], outs[1]);
-
-        for gene_i in 0..self.gene_size {
-            // infect
-            let inf_roll: f64 = rand::thread_rng().gen();
-            if inf_roll < self.inf_prob {
-                self.genomes[outs[1]][gene_i] = self.genomes[outs[0]][gene_i];
-            }
-
-            // mutate
-            let mut_roll: f64 = rand::thread_rng().gen();
-            if mut_roll < self.mut_prob {
-                self.genomes[outs[1]][gene_i] = (self.genomes[outs[1]][gene_i] + 1) % 2;
-            }
-        }
-    }
-
-    fn get_fitness(&self) -> Vec<f64> {
-        let mut fit_vec: Vec<f64> = Vec::new();
-        for i in 0..self.pop_size {
-            fit_vec.push((self.fitness)(&self.genomes[i]));
-        }
-        return fit_vec
-    }
-
-    pub fn evolve(&mut self, n_steps: u32) -> Vec<f64> {
-        for _ in 0..n_steps {
-            self.step();
-            println!("Step");
-        }
-        
-        return self.get_fitness();
+        self.log(format!("{} beats {}", outs[0], outs[1]));
+        self.reproduce(outs[0], outs[1]);
+        // fitness plays no part here, so the known value goes stale
+        self.stale[outs[1]] = true;
     }
 
     pub fn evolve_novelty(&mut self, n_steps: u32) -> Vec<f64> {
         for _ in 0..n_steps {
             self.step_novelty();
-            println!("Novelty Step");
+            self.log("Novelty Step".to_string());
         }
 
         return self.get_fitness();
     }
 }
 
+// indices from fittest to least fit
+fn ranking(fits: &Vec<f64>) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..fits.len()).collect();
+    order.sort_by(|a, b| fits[*b].total_cmp(&fits[*a]));
+    return order
+}
+
+fn pick_weighted<R: Rng>(weights: &Vec<f64>, rng: &mut R) -> usize {
+    let total: f64 = weights.iter().sum();
+    let mut roll = rng.gen::<f64>() * total;
+    for i in 0..weights.len() {
+        roll -= weights[i];
+        if roll < 0.0 {
+            return i
+        }
+    }
+    return weights.len() - 1
+}
+
 #[cfg(test)]
 mod test_mga {
     use super::*;
+    use crate::genome::{Crossover, MixedGenome, RealParams};
 
     fn ffunc_test(genes: &Vec<u8>) -> f64 {
         let mut gene_sum = 0;
@@ -263,4 +441,122 @@ mod test_mga {
         assert_eq!(out[1], 0);
 
     }
-}
\ No newline at end of file
+
+    fn ffunc_mixed(genome: &MixedGenome) -> f64 {
+        return (0..genome.bits.len()).map(|k| (genome.bits[k] as f64) * genome.weights[k]).sum()
+    }
+
+    #[test]
+    fn test_evolve_mixed() {
+        let params = RealParams { crossover: Crossover::Copy, ..RealParams::default() };
+        let mut mga = MGA::with_params(ffunc_mixed, 3, 2, 2, 0.0, 1.0, params);
+        mga.genomes = vec![MixedGenome { bits: vec![1, 0], weights: vec![0.5, 2.0] },
+                           MixedGenome { bits: vec![1, 1], weights: vec![1.0, 2.0] },
+                           MixedGenome { bits: vec![0, 0], weights: vec![0.0, 0.0] }];
+
+        // full infection with copying spreads the best genome to everyone
+        let fit_history = mga.evolve(30);
+        assert!(fit_history.iter().all(|f| *f == 3.0))
+    }
+
+    #[test]
+    fn test_compete_novelty_real() {
+        fn ffunc_real(genes: &Vec<f64>) -> f64 {
+            genes.iter().sum()
+        }
+        let mut mga = MGA::new(ffunc_real, 3, 2, 2, 0.0, 1.0);
+        mga.genomes = vec![vec![0.0, 0.0],
+                           vec![0.1, 0.0],
+                           vec![3.0, 1.0]];
+
+        // 2 is far from everyone
+        let out = mga.compete_novelty(1, 2);
+        assert_eq!(out, [2, 1])
+    }
+
+    #[test]
+    fn test_selection_schemes() {
+        let schemes = vec![Selection::Tournament(4), Selection::Proportional, Selection::Rank,
+                           Selection::MuPlusLambda(10), Selection::MuCommaLambda(20),
+                           Selection::SteadyState { elites: 2 }];
+        for scheme in schemes {
+            let mut mga = MGA::new(ffunc_test, 10, 20, 2, 0.01, 0.5);
+            mga.genomes = vec![vec![0; 20]; 10];
+            mga.genomes[0] = vec![1; 20];
+            mga.selection = scheme.clone();
+            mga.quiet = true;
+
+            // one perfect genome among zeros has to take over
+            let fits = mga.evolve(200);
+            let mean = fits.iter().sum::<f64>() / 10.0;
+            assert!(mean > 10.0, "{:?} only reached {}", scheme, mean);
+        }
+    }
+
+    #[test]
+    fn test_elitism() {
+        for scheme in vec![Selection::MuPlusLambda(5), Selection::SteadyState { elites: 1 }] {
+            let mut mga = MGA::new(ffunc_test, 6, 8, 2, 0.2, 0.5);
+            mga.selection = scheme;
+            mga.quiet = true;
+            mga.genomes[3] = vec![1; 8];
+            for _ in 0..50 {
+                mga.step();
+                assert!(mga.genomes.contains(&vec![1; 8]));
+            }
+        }
+    }
+
+    #[test]
+    fn test_observer() {
+        let seen = std::rc::Rc::new(std::cell::Cell::new(0));
+        let counter = seen.clone();
+        let mut mga = MGA::new(ffunc_test, 4, 3, 2, 0.1, 0.5);
+        mga.selection = Selection::Rank;
+        mga.observer = Some(Box::new(move |t: u32, genomes: &[Vec<u8>], fits: &[f64]| {
+            assert_eq!(genomes.len(), fits.len());
+            counter.set(t + 1);
+        }));
+        mga.quiet = true;
+        mga.evolve(7);
+        assert_eq!(seen.get(), 7)
+    }
+
+    #[test]
+    fn test_observer_fitness_current() {
+        let schemes = vec![Selection::Microbial, Selection::Tournament(3), Selection::Proportional,
+                           Selection::Rank, Selection::MuPlusLambda(4), Selection::MuCommaLambda(8),
+                           Selection::SteadyState { elites: 1 }];
+        for scheme in schemes {
+            let last = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+            let keep = last.clone();
+            let mut mga = MGA::new(ffunc_test, 6, 8, 2, 0.2, 0.5);
+            mga.selection = scheme;
+            mga.observer = Some(Box::new(move |_t: u32, genomes: &[Vec<u8>], fits: &[f64]| {
+                for i in 0..genomes.len() {
+                    assert_eq!(fits[i], ffunc_test(&genomes[i]));
+                }
+                *keep.borrow_mut() = fits.to_vec();
+            }));
+            mga.quiet = true;
+            let fits = mga.evolve(20);
+            assert_eq!(fits, *last.borrow());
+            // genomes changed between calls get scored again
+            mga.genomes[0] = vec![1; 8];
+            mga.evolve(5);
+        }
+    }
+
+    #[test]
+    fn test_nan_fitness() {
+        fn ffunc_nan(genes: &Vec<u8>) -> f64 {
+            if genes[0] == 1 { f64::NAN } else { ffunc_test(genes) }
+        }
+        for scheme in vec![Selection::Tournament(3), Selection::Rank, Selection::MuPlusLambda(4)] {
+            let mut mga = MGA::new(ffunc_nan, 6, 8, 2, 0.2, 0.5);
+            mga.selection = scheme;
+            mga.quiet = true;
+            mga.evolve(20);
+        }
+    }
+}
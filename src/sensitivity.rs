@@ -0,0 +1,165 @@
+use crate::continuation::Parameter;
+use crate::glv::GLV;
+use crate::integrate;
+use crate::utils;
+use rand::prelude::*;
+
+// knobs for the morris screening
+#[derive(Debug, Clone)]
+pub struct MorrisParams {
+    pub trajectories: usize,  // one at a time walks through parameter space
+    pub levels: usize,  // grid points per parameter
+    pub spread: f64,  // each parameter ranges over base * (1 +- spread)
+    pub start: f64,  // every species starts here
+    pub threshold: f64,  // survivors are above this at steady state
+    pub dt: f64,
+    pub tol: f64,
+    pub max_time: f64,
+}
+
+impl Default for MorrisParams {
+    fn default() -> MorrisParams {
+        MorrisParams {
+            trajectories: 20,
+            levels: 4,
+            spread: 0.5,
+            start: 0.5,
+            threshold: 0.01,
+            dt: 0.01,
+            tol: 1e-6,
+            max_time: 200.0,
+        }
+    }
+}
+
+// elementary effects of one parameter on richness, in units of its range
+#[derive(Debug, Clone)]
+pub struct ElementaryEffects {
+    pub param: Parameter,
+    pub mu: f64,  // mean effect, signed
+    pub mu_star: f64,  // mean absolute effect, the importance
+    pub sigma: f64,  // spread of the effect, nonlinearity and interactions
+}
+
+// every r_i and every link that is present, a_ij != 0 off the diagonal
+pub fn factors(glv: &GLV) -> Vec<Parameter> {
+    let mut out: Vec<Parameter> = (0..glv.n).map(Parameter::Growth).collect();
+    for i in 0..glv.n {
+        for j in 0..glv.n {
+            if (i != j) && (glv.a[i][j] != 0.0) {
+                out.push(Parameter::Interaction(i, j));
+            }
+        }
+    }
+    return out
+}
+
+fn base_value(glv: &GLV, param: Parameter) -> f64 {
+    match param {
+        Parameter::Growth(i) => glv.r[i],
+        Parameter::Interaction(i, j) => glv.a[i][j],
+        Parameter::OffDiagonalScale => 1.0,
+    }
+}
+
+fn richness_at(glv: &GLV, params: &MorrisParams) -> f64 {
+    let start = vec![params.start; glv.n];
+    let steady = integrate::run_to_steady(glv, start, params.dt, params.tol, params.max_time);
+    return utils::richness(&steady.state, params.threshold) as f64
+}
+
+// morris screening of steady state richness. each trajectory starts at a
+// random grid point and moves one parameter at a time by delta in a random
+// order, so k parameters cost k + 1 runs to steady state per trajectory.
+// results are ranked by mu_star, largest first
+pub fn morris(glv: &GLV, params: &MorrisParams) -> Vec<ElementaryEffects> {
+    let mut rng = thread_rng();
+    let factors = factors(glv);
+    assert!(params.trajectories > 0, "morris needs at least one trajectory");
+    let k = factors.len();
+    let base: Vec<f64> = factors.iter().map(|f| base_value(glv, *f)).collect();
+    let levels = params.levels.max(2);
+    let step = 1.0 / ((levels - 1) as f64);
+    let delta = ((levels / 2) as f64) * step;
+    let set = |model: &mut GLV, f: usize, u: f64| {
+        let value = base[f] * (1.0 + params.spread * (2.0 * u - 1.0));
+        factors[f].apply(model, glv, value);
+    };
+
+    let mut effects: Vec<Vec<f64>> = vec![Vec::with_capacity(params.trajectories); k];
+    let mut order: Vec<usize> = (0..k).collect();
+    for _ in 0..params.trajectories {
+        let mut model = glv.clone();
+        let mut unit: Vec<f64> = Vec::with_capacity(k);
+        for f in 0..k {
+            let u = (rng.gen_range(0..levels) as f64) * step;
+            set(&mut model, f, u);
+            unit.push(u);
+        }
+        let mut y = richness_at(&model, params);
+
+        order.shuffle(&mut rng);
+        for f in order.iter() {
+            // up or down with equal odds when both stay on the grid
+            let can_up = unit[*f] + delta <= 1.0 + 1e-12;
+            let can_down = unit[*f] - delta >= -1e-12;
+            let up = if can_up && can_down { rng.gen::<bool>() } else { can_up };
+            let sign = if up { 1.0 } else { -1.0 };
+            unit[*f] += sign * delta;
+            set(&mut model, *f, unit[*f]);
+            let y_next = richness_at(&model, params);
+            effects[*f].push(sign * (y_next - y) / delta);
+            y = y_next;
+        }
+    }
+
+    let mut out: Vec<ElementaryEffects> = Vec::with_capacity(k);
+    for f in 0..k {
+        let m = effects[f].len() as f64;
+        let mu = effects[f].iter().sum::<f64>() / m;
+        let mu_star = effects[f].iter().map(|e| e.abs()).sum::<f64>() / m;
+        let var = effects[f].iter().map(|e| (e - mu) * (e - mu)).sum::<f64>() / (m - 1.0).max(1.0);
+        out.push(ElementaryEffects {
+            param: factors[f],
+            mu: mu,
+            mu_star: mu_star,
+            sigma: var.sqrt(),
+        });
+    }
+    out.sort_by(|x, y| y.mu_star.total_cmp(&x.mu_star));
+    return out
+}
+
+#[cfg(test)]
+mod test_sensitivity {
+    use super::*;
+
+    #[test]
+    fn test_factors() {
+        let mut glv = GLV::new(3);
+        glv.a[0][1] = 0.5;
+        glv.a[2][0] = -0.2;
+        let f = factors(&glv);
+
+        assert_eq!(f.len(), 5);
+        assert_eq!(f[3], Parameter::Interaction(0, 1));
+        assert_eq!(f[4], Parameter::Interaction(2, 0))
+    }
+
+    #[test]
+    fn test_morris_ranks_critical_link() {
+        // species 0 survives only while a_01 < 1, the rest can not change who
+        // survives and r never moves the equilibrium
+        let mut glv = GLV::new(3);
+        glv.a[0][1] = 1.0;
+        glv.a[1][0] = 0.5;
+        let params = MorrisParams { trajectories: 10, spread: 0.3, ..MorrisParams::default() };
+        let ranked = morris(&glv, &params);
+
+        assert_eq!(ranked[0].param, Parameter::Interaction(0, 1));
+        assert!(ranked[0].mu < 0.0);
+        for e in ranked.iter().skip(1) {
+            assert_eq!(e.mu_star, 0.0);
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use std::ops::Index;
+
+// which states a simulation keeps around
+#[derive(Debug, Clone, PartialEq)]
+pub enum Record {
+    Full,  // every step
+    Final,  // only the end state
+    Every(usize),  // every k-th step plus the end state
+    Times(Vec<f64>),  // first step at or after each requested time, times sorted
+    Summary,  // no states, just per species mean, min and max
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Summary {
+    pub mean: Vec<f64>,
+    pub min: Vec<f64>,
+    pub max: Vec<f64>,
+}
+
+// something that was done to the system during a run, eg an intervention
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    pub time: f64,
+    pub step: usize,  // applied before integrating this step
+    pub index: usize,  // which entry of the schedule it was
+}
+
+// flat storage for simulation output, one row of n values per recorded state
+#[derive(Debug, Clone)]
+pub struct Trajectory {
+    pub n: usize,  // Number of variables per state
+    pub t: Vec<f64>,  // Time of each recorded state
+    pub data: Vec<f64>,  // Recorded states laid end to end
+    pub summary: Option<Summary>,  // Only filled for Record::Summary
+    pub events: Vec<Event>,  // Interventions applied during the run
+}
+
+impl Trajectory {
+    pub fn with_capacity(n: usize, states: usize) -> Trajectory {
+        Trajectory {
+            n: n,
+            t: Vec::with_capacity(states),
+            data: Vec::with_capacity(n * states),
+            summary: None,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, t: f64, state: &Vec<f64>) {
+        assert_eq!(state.len(), self.n);
+        self.t.push(t);
+        self.data.extend_from_slice(state);
+    }
+
+    pub fn len(&self) -> usize {
+        if self.n == 0 {
+            return 0
+        }
+        return self.data.len() / self.n
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.data.is_empty()
+    }
+
+    pub fn last(&self) -> Option<&[f64]> {
+        if self.is_empty() {
+            return None
+        }
+        return Some(&self[self.len() - 1])
+    }
+
+    pub fn to_nested(&self) -> Vec<Vec<f64>> {
+        let mut out: Vec<Vec<f64>> = Vec::with_capacity(self.len());
+        for i in 0..self.len() {
+            out.push(self[i].to_vec());
+        }
+        return out
+    }
+}
+
+// feeds states from an integrator loop into a trajectory according to a Record
+pub struct Recorder {
+    record: Record,
+    traj: Trajectory,
+    next_time: usize,  // index of the next requested output time
+    steps_seen: usize,  // states folded into the running summary
+    last_step: usize,  // step index of the last state pushed
+}
+
+impl Recorder {
+    pub fn new(record: &Record, n: usize, steps: usize) -> Recorder {
+        // the rows line up with the requested times, so they have to come in order
+        if let Record::Times(times) = record {
+            assert!(times.windows(2).all(|w| w[0] <= w[1]), "Record::Times needs sorted times");
+        }
+        let capacity = match record {
+            Record::Full => steps,
+            Record::Final => 1,
+            Record::Every(k) => steps / k.max(&1) + 2,
+            Record::Times(times) => times.len(),
+            Record::Summary => 0,
+        };
+        let mut traj = Trajectory::with_capacity(n, capacity);
+        if *record == Record::Summary {
+            traj.summary = Some(Summary {
+                mean: vec![0.0; n],
+                min: vec![f64::INFINITY; n],
+                max: vec![f64::NEG_INFINITY; n],
+            });
+        }
+        Recorder {
+            record: record.clone(),
+            traj: traj,
+            next_time: 0,
+            steps_seen: 0,
+            last_step: usize::MAX,
+        }
+    }
+
+    pub fn observe(&mut self, step: usize, t: f64, x: &Vec<f64>) {
+        match &self.record {
+            Record::Full => self.push(step, t, x),
+            Record::Final => (),
+            Record::Every(k) => {
+                if step.is_multiple_of((*k).max(1)) {
+                    self.push(step, t, x);
+                }
+            },
+            Record::Times(times) => {
+                // several requested times can fall inside one step
+                let mut hits = 0;
+                while (self.next_time + hits < times.len()) && (times[self.next_time + hits] <= t) {
+                    hits += 1;
+                }
+                for _ in 0..hits {
+                    self.traj.push(t, x);
+                }
+                self.next_time += hits;
+            },
+            Record::Summary => {
+                self.steps_seen += 1;
+                let w = 1.0 / (self.steps_seen as f64);
+                if let Some(summary) = self.traj.summary.as_mut() {
+                    for i in 0..x.len() {
+                        // running mean so long runs do not lose precision
+                        summary.mean[i] += (x[i] - summary.mean[i]) * w;
+                        summary.min[i] = summary.min[i].min(x[i]);
+                        summary.max[i] = summary.max[i].max(x[i]);
+                    }
+                }
+            },
+        }
+    }
+
+    pub fn event(&mut self, event: Event) {
+        self.traj.events.push(event);
+    }
+
+    // the end state is always kept for Final and Every, even off the stride
+    pub fn finish(mut self, step: usize, t: f64, x: &Vec<f64>) -> Trajectory {
+        match self.record {
+            Record::Final => self.push(step, t, x),
+            Record::Every(_) if self.last_step != step => self.push(step, t, x),
+            _ => (),
+        }
+        return self.traj
+    }
+
+    fn push(&mut self, step: usize, t: f64, x: &Vec<f64>) {
+        self.traj.push(t, x);
+        self.last_step = step;
+    }
+}
+
+// lets callers keep writing traj[t][species] like with the old nested vecs
+impl Index<usize> for Trajectory {
+    type Output = [f64];
+
+    fn index(&self, i: usize) -> &[f64] {
+        return &self.data[(i * self.n)..((i + 1) * self.n)]
+    }
+}
+
+#[cfg(test)]
+mod test_trajectory {
+    use super::*;
+
+    #[test]
+    fn test_push_index() {
+        let mut traj = Trajectory::with_capacity(2, 3);
+        traj.push(0.0, &vec![1.0, 2.0]);
+        traj.push(0.1, &vec![3.0, 4.0]);
+
+        assert_eq!(traj.len(), 2);
+        assert_eq!(traj[1][0], 3.0);
+        assert_eq!(traj.last(), Some(&[3.0, 4.0][..]));
+        assert_eq!(traj.to_nested(), vec![vec![1.0, 2.0], vec![3.0, 4.0]])
+    }
+
+    #[test]
+    fn test_recorder_every() {
+        let mut rec = Recorder::new(&Record::Every(2), 1, 5);
+        for step in 0..5 {
+            rec.observe(step, step as f64, &vec![step as f64]);
+        }
+        let traj = rec.finish(4, 4.0, &vec![4.0]);
+
+        assert_eq!(traj.t, vec![0.0, 2.0, 4.0])
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_recorder_unsorted_times() {
+        Recorder::new(&Record::Times(vec![0.5, 0.2]), 1, 10);
+    }
+
+    #[test]
+    fn test_recorder_summary() {
+        let mut rec = Recorder::new(&Record::Summary, 2, 3);
+        rec.observe(0, 0.0, &vec![1.0, 0.0]);
+        rec.observe(1, 0.1, &vec![3.0, 2.0]);
+        let traj = rec.finish(1, 0.1, &vec![3.0, 2.0]);
+        let summary = traj.summary.clone().unwrap();
+
+        assert!(traj.is_empty());
+        assert_eq!(summary.mean, vec![2.0, 1.0]);
+        assert_eq!(summary.min, vec![1.0, 0.0]);
+        assert_eq!(summary.max, vec![3.0, 2.0])
+    }
+}
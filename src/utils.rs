@@ -21,9 +21,29 @@ pub fn hamming<T: std::cmp::Eq>(x: &Vec<T>, y: &Vec<T>) -> usize {
     return dist
 }
 
+// number of species above the detection threshold
+pub fn richness(state: &[f64], threshold: f64) -> usize {
+    let mut count: usize = 0;
+    for i in 0..state.len() {
+        if state[i] > threshold {
+            count += 1;
+        }
+    }
+    return count
+}
+
+// rows and columns idx of a square matrix, in the order given
+pub fn submatrix<T: Copy>(m: &Vec<Vec<T>>, idx: &Vec<usize>) -> Vec<Vec<T>> {
+    let mut out: Vec<Vec<T>> = Vec::with_capacity(idx.len());
+    for i in idx {
+        out.push(idx.iter().map(|j| m[*i][*j]).collect());
+    }
+    return out
+}
+
 #[cfg(test)]
 mod test_utils {
-    use crate::utils::hamming;
+    use crate::utils::{hamming, richness};
     #[test]
     fn test_hamming() {
         let x1 = vec![2, 3, 0];
@@ -31,4 +51,9 @@ mod test_utils {
 
         assert_eq!(hamming(&x1, &x2), 1)
     }
+
+    #[test]
+    fn test_richness() {
+        assert_eq!(richness(&[0.5, 0.001, 0.02], 0.01), 2)
+    }
 }
\ No newline at end of file
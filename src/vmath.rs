@@ -83,6 +83,293 @@ impl VMath for Vec<Vec<f64>> {
     }
 }
 
+// solves m x = b by gaussian elimination with partial pivoting, None when
+// m is (numerically) singular
+pub fn solve(m: &Vec<Vec<f64>>, b: &Vec<f64>) -> Option<Vec<f64>> {
+    let n = b.len();
+    assert_eq!(m.len(), n);
+    let mut aug: Vec<Vec<f64>> = Vec::with_capacity(n);
+    for i in 0..n {
+        assert_eq!(m[i].len(), n);
+        let mut row = m[i].clone();
+        row.push(b[i]);
+        aug.push(row);
+    }
+
+    for col in 0..n {
+        // biggest pivot keeps the rounding under control
+        let mut pivot = col;
+        for row in (col + 1)..n {
+            if aug[row][col].abs() > aug[pivot][col].abs() {
+                pivot = row;
+            }
+        }
+        if aug[pivot][col].abs() < 1e-12 {
+            return None
+        }
+        aug.swap(col, pivot);
+        for row in (col + 1)..n {
+            let factor = aug[row][col] / aug[col][col];
+            for k in col..(n + 1) {
+                aug[row][k] -= factor * aug[col][k];
+            }
+        }
+    }
+
+    let mut x = vec![0.0; n];
+    for i in (0..n).rev() {
+        let mut acc = aug[i][n];
+        for j in (i + 1)..n {
+            acc -= aug[i][j] * x[j];
+        }
+        x[i] = acc / aug[i][i];
+    }
+    return Some(x)
+}
+
+// eigenvalues of a general real square matrix as (re, im) pairs, in no
+// particular order. reduces to hessenberg form by elimination and then runs
+// the shifted QR iteration, both after Numerical Recipes' elmhes and hqr.
+// None when the iteration does not converge
+pub fn eigenvalues(m: &Vec<Vec<f64>>) -> Option<Vec<(f64, f64)>> {
+    let n = m.len();
+    if n == 0 {
+        return Some(Vec::new())
+    }
+    // 1 based copy so the indices line up with the textbook version
+    let mut a = vec![vec![0.0; n + 1]; n + 1];
+    for i in 0..n {
+        assert_eq!(m[i].len(), n);
+        for j in 0..n {
+            a[i + 1][j + 1] = m[i][j];
+        }
+    }
+    hessenberg(&mut a, n);
+    return hqr(&mut a, n)
+}
+
+fn hessenberg(a: &mut Vec<Vec<f64>>, n: usize) {
+    for m in 2..n {
+        let mut x: f64 = 0.0;
+        let mut i = m;
+        for j in m..(n + 1) {
+            if a[j][m - 1].abs() > x.abs() {
+                x = a[j][m - 1];
+                i = j;
+            }
+        }
+        if i != m {
+            for j in (m - 1)..(n + 1) {
+                let tmp = a[i][j];
+                a[i][j] = a[m][j];
+                a[m][j] = tmp;
+            }
+            for j in 1..(n + 1) {
+                a[j].swap(i, m);
+            }
+        }
+        if x != 0.0 {
+            for i in (m + 1)..(n + 1) {
+                let mut y = a[i][m - 1];
+                if y != 0.0 {
+                    y /= x;
+                    a[i][m - 1] = y;
+                    for j in m..(n + 1) {
+                        a[i][j] -= y * a[m][j];
+                    }
+                    for j in 1..(n + 1) {
+                        a[j][m] += y * a[j][i];
+                    }
+                }
+            }
+        }
+    }
+    // elimination leaves its multipliers under the subdiagonal
+    for i in 3..(n + 1) {
+        for j in 1..(i - 1) {
+            a[i][j] = 0.0;
+        }
+    }
+}
+
+fn sign(a: f64, b: f64) -> f64 {
+    if b >= 0.0 { a.abs() } else { -a.abs() }
+}
+
+fn hqr(a: &mut Vec<Vec<f64>>, n: usize) -> Option<Vec<(f64, f64)>> {
+    let mut wr = vec![0.0; n + 1];
+    let mut wi = vec![0.0; n + 1];
+    let mut anorm = 0.0;
+    for i in 1..(n + 1) {
+        for j in (i.max(2) - 1)..(n + 1) {
+            anorm += a[i][j].abs();
+        }
+    }
+
+    let mut nn = n;
+    let mut t = 0.0;
+    let (mut p, mut q, mut r, mut x, mut y, mut z, mut w): (f64, f64, f64, f64, f64, f64, f64);
+    while nn >= 1 {
+        let mut its = 0;
+        loop {
+            // look for a single small subdiagonal element
+            let mut l = nn;
+            while l >= 2 {
+                let mut s = a[l - 1][l - 1].abs() + a[l][l].abs();
+                if s == 0.0 {
+                    s = anorm;
+                }
+                if a[l][l - 1].abs() + s == s {
+                    a[l][l - 1] = 0.0;
+                    break;
+                }
+                l -= 1;
+            }
+            x = a[nn][nn];
+            if l == nn {
+                // one root found
+                wr[nn] = x + t;
+                wi[nn] = 0.0;
+                nn -= 1;
+                break;
+            }
+            y = a[nn - 1][nn - 1];
+            w = a[nn][nn - 1] * a[nn - 1][nn];
+            if l == nn - 1 {
+                // two roots found
+                p = 0.5 * (y - x);
+                q = p * p + w;
+                z = q.abs().sqrt();
+                x += t;
+                if q >= 0.0 {
+                    z = p + sign(z, p);
+                    wr[nn - 1] = x + z;
+                    wr[nn] = x + z;
+                    if z != 0.0 {
+                        wr[nn] = x - w / z;
+                    }
+                    wi[nn - 1] = 0.0;
+                    wi[nn] = 0.0;
+                } else {
+                    wr[nn - 1] = x + p;
+                    wr[nn] = x + p;
+                    wi[nn - 1] = -z;
+                    wi[nn] = z;
+                }
+                nn -= 2;
+                break;
+            }
+
+            if its == 60 {
+                return None
+            }
+            if (its == 10) || (its == 20) {
+                // exceptional shift
+                t += x;
+                for i in 1..(nn + 1) {
+                    a[i][i] -= x;
+                }
+                let s = a[nn][nn - 1].abs() + a[nn - 1][nn - 2].abs();
+                x = 0.75 * s;
+                y = x;
+                w = -0.4375 * s * s;
+            }
+            its += 1;
+
+            // form the shift and look for two consecutive small subdiagonals
+            let mut m = nn - 2;
+            loop {
+                z = a[m][m];
+                r = x - z;
+                let s = y - z;
+                p = (r * s - w) / a[m + 1][m] + a[m][m + 1];
+                q = a[m + 1][m + 1] - z - r - s;
+                r = a[m + 2][m + 1];
+                let s = p.abs() + q.abs() + r.abs();
+                p /= s;
+                q /= s;
+                r /= s;
+                if m == l {
+                    break;
+                }
+                let u = a[m][m - 1].abs() * (q.abs() + r.abs());
+                let v = p.abs() * (a[m - 1][m - 1].abs() + z.abs() + a[m + 1][m + 1].abs());
+                if u + v == v {
+                    break;
+                }
+                m -= 1;
+            }
+            for i in (m + 2)..(nn + 1) {
+                a[i][i - 2] = 0.0;
+                if i != m + 2 {
+                    a[i][i - 3] = 0.0;
+                }
+            }
+
+            // double QR step on rows l..nn and columns m..nn
+            let mut k = m;
+            while k < nn {
+                if k != m {
+                    p = a[k][k - 1];
+                    q = a[k + 1][k - 1];
+                    r = 0.0;
+                    if k != nn - 1 {
+                        r = a[k + 2][k - 1];
+                    }
+                    x = p.abs() + q.abs() + r.abs();
+                    if x != 0.0 {
+                        p /= x;
+                        q /= x;
+                        r /= x;
+                    }
+                }
+                let s = sign((p * p + q * q + r * r).sqrt(), p);
+                if s != 0.0 {
+                    if k == m {
+                        if l != m {
+                            a[k][k - 1] = -a[k][k - 1];
+                        }
+                    } else {
+                        a[k][k - 1] = -s * x;
+                    }
+                    p += s;
+                    x = p / s;
+                    y = q / s;
+                    z = r / s;
+                    q /= p;
+                    r /= p;
+                    for j in k..(nn + 1) {
+                        p = a[k][j] + q * a[k + 1][j];
+                        if k != nn - 1 {
+                            p += r * a[k + 2][j];
+                            a[k + 2][j] -= p * z;
+                        }
+                        a[k + 1][j] -= p * y;
+                        a[k][j] -= p * x;
+                    }
+                    let mmin = if nn < k + 3 { nn } else { k + 3 };
+                    for i in l..(mmin + 1) {
+                        p = x * a[i][k] + y * a[i][k + 1];
+                        if k != nn - 1 {
+                            p += z * a[i][k + 2];
+                            a[i][k + 2] -= p * r;
+                        }
+                        a[i][k + 1] -= p * q;
+                        a[i][k] -= p;
+                    }
+                }
+                k += 1;
+            }
+        }
+    }
+
+    let mut out: Vec<(f64, f64)> = Vec::with_capacity(n);
+    for i in 1..(n + 1) {
+        out.push((wr[i], wi[i]));
+    }
+    return Some(out)
+}
+
 #[cfg(test)]
 mod test_vmath {
     use super::*;
@@ -98,6 +385,42 @@ mod test_vmath {
         assert_eq!(dp, 11.0)
     }
 
+    #[test]
+    fn test_solve() {
+        let m = vec![vec![0.0, 2.0],
+                     vec![1.0, 1.0]];
+        let x = solve(&m, &vec![4.0, 3.0]).unwrap();
+        assert!((x[0] - 1.0).abs() < 1e-12);
+        assert!((x[1] - 2.0).abs() < 1e-12);
+
+        let singular = vec![vec![1.0, 2.0],
+                            vec![2.0, 4.0]];
+        assert!(solve(&singular, &vec![1.0, 1.0]).is_none())
+    }
+
+    #[test]
+    fn test_eigenvalues() {
+        // rotation plus scaling has eigenvalues 1 +- 2i
+        let rot = vec![vec![1.0, -2.0],
+                       vec![2.0, 1.0]];
+        let mut ev = eigenvalues(&rot).unwrap();
+        ev.sort_by(|p, q| p.1.total_cmp(&q.1));
+        assert!((ev[0].0 - 1.0).abs() < 1e-12 && (ev[0].1 + 2.0).abs() < 1e-12);
+        assert!((ev[1].0 - 1.0).abs() < 1e-12 && (ev[1].1 - 2.0).abs() < 1e-12);
+
+        // companion matrix of (x - 1)(x - 2)(x - 3)(x + 4)
+        let comp = vec![vec![2.0, 13.0, -38.0, 24.0],
+                        vec![1.0, 0.0, 0.0, 0.0],
+                        vec![0.0, 1.0, 0.0, 0.0],
+                        vec![0.0, 0.0, 1.0, 0.0]];
+        let mut re: Vec<f64> = eigenvalues(&comp).unwrap().iter().map(|e| e.0).collect();
+        re.sort_by(|p, q| p.total_cmp(q));
+        let known = vec![-4.0, 1.0, 2.0, 3.0];
+        for i in 0..4 {
+            assert!((re[i] - known[i]).abs() < 1e-9);
+        }
+    }
+
     #[test]
     fn test_eadd_float() {
         let v1: Vec<f64> = vec![1.0, 2.0];